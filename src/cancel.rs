@@ -0,0 +1,163 @@
+use crate::error::{ProcError, ProcResult};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex, Weak};
+use std::time::{Duration, Instant};
+
+/// Cooperative cancellation signal shared between a [`Proc`](crate::Proc) and the
+/// function it executes. Cancelling a token also cancels all tokens derived from it.
+#[derive(Clone, Default)]
+pub struct CancellationToken(Arc<Inner>);
+
+#[derive(Default)]
+struct Inner {
+    cancelled: AtomicBool,
+    children: Mutex<Vec<Weak<Inner>>>,
+    signal: Condvar,
+}
+
+impl CancellationToken {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Signals cancellation to this token and all of its children
+    pub fn cancel(&self) {
+        self.0.cancel();
+    }
+
+    #[inline]
+    pub fn is_cancelled(&self) -> bool {
+        self.0.cancelled.load(Ordering::Acquire)
+    }
+
+    /// Returns [`ProcError::Cancelled`] if cancellation was requested,
+    /// allowing early returns through `?`
+    pub fn check(&self) -> ProcResult<()> {
+        if self.is_cancelled() {
+            Err(ProcError::Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Blocks the current thread until cancellation is requested
+    pub fn wait(&self) {
+        let mut children = self.0.children.lock().unwrap();
+        while !self.is_cancelled() {
+            children = self.0.signal.wait(children).unwrap();
+        }
+    }
+
+    /// Blocks the current thread until cancellation is requested or the timeout elapses.
+    /// Returns whether the token was cancelled
+    pub fn wait_timeout(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        let mut children = self.0.children.lock().unwrap();
+        while !self.is_cancelled() {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                break;
+            }
+            children = self.0.signal.wait_timeout(children, remaining).unwrap().0;
+        }
+        self.is_cancelled()
+    }
+
+    /// Derives a token which is cancelled together with this one,
+    /// but can also be cancelled on its own
+    pub fn child_token(&self) -> Self {
+        let child = Self::new();
+        let mut children = self.0.children.lock().unwrap();
+        if self.is_cancelled() {
+            child.cancel();
+        } else {
+            children.retain(|child| child.strong_count() > 0);
+            children.push(Arc::downgrade(&child.0));
+        }
+        child
+    }
+}
+
+impl Inner {
+    fn cancel(&self) {
+        let children = {
+            let mut children = self.children.lock().unwrap();
+            if self.cancelled.swap(true, Ordering::AcqRel) {
+                return;
+            }
+            self.signal.notify_all();
+            std::mem::take(&mut *children)
+        };
+        for child in children.iter().filter_map(Weak::upgrade) {
+            child.cancel();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::proc::Proc;
+    use crate::proc_ext::ProcExt;
+    use crate::{cancellable_blocking, cancellable_thread};
+
+    #[test]
+    fn cancels_children() {
+        let parent = CancellationToken::new();
+        let child = parent.child_token();
+        let grandchild = child.child_token();
+        child.cancel();
+        assert!(!parent.is_cancelled());
+        assert!(grandchild.is_cancelled());
+        parent.cancel();
+        assert!(parent.child_token().is_cancelled());
+    }
+
+    #[test]
+    fn wait_timeout() {
+        let token = CancellationToken::new();
+        assert!(!token.wait_timeout(Duration::from_millis(10)));
+        token.cancel();
+        assert!(token.wait_timeout(Duration::from_secs(5)));
+    }
+
+    #[test]
+    fn forget_thread() {
+        let mut proc = cancellable_thread(|token| {
+            token.wait();
+            token.check()?;
+            Ok(())
+        });
+        proc.forget();
+        assert!(proc.join().unwrap_err().is_cancelled());
+    }
+
+    #[test]
+    fn forget_propagates_through_combinators() {
+        let (started_tx, started_rx) = flume::bounded(2);
+        let worker = |started: flume::Sender<()>| {
+            cancellable_thread(move |token| {
+                started.send(())?;
+                token.wait();
+                Ok(())
+            })
+        };
+        let mut proc = worker(started_tx.clone()).and_then(worker(started_tx));
+        started_rx.recv().unwrap();
+        started_rx.recv().unwrap();
+        proc.forget();
+        drop(proc);
+    }
+
+    #[test]
+    fn cancels_right_on_failure() {
+        let right = cancellable_thread(|token| {
+            token.wait();
+            Ok(())
+        });
+        let mut proc = cancellable_blocking(|_| -> anyhow::Result<()> { anyhow::bail!("failed") })
+            .and_then(right);
+        assert!(proc.join().unwrap_err().user_error().is_some());
+    }
+}
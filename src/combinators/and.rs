@@ -1,4 +1,7 @@
-use crate::proc::Proc;
+use crate::drop_policy::DropPolicy;
+use crate::error::{ProcError, ProcResult};
+use crate::proc::{join_until, Proc, ProcStatus};
+use std::time::Instant;
 
 /// [`Proc`] combinator that allows combining the results of two units of execution
 /// sharing the same result types
@@ -9,6 +12,28 @@ where
 {
     pub(crate) left: L,
     pub(crate) right: R,
+    pub(crate) left_joined: bool,
+    pub(crate) drop_policy: DropPolicy,
+}
+
+impl<L, R> AndThenProc<L, R>
+where
+    L: Proc + Send,
+    R: Proc + Send,
+{
+    fn join_until(&mut self, deadline: Option<Instant>) -> ProcResult<R::Output> {
+        if !self.left_joined {
+            match join_until(&mut self.left, deadline) {
+                Ok(_) => self.left_joined = true,
+                Err(ProcError::TimedOut) => return Err(ProcError::TimedOut),
+                Err(err) => {
+                    self.right.forget();
+                    return Err(err);
+                }
+            }
+        }
+        join_until(&mut self.right, deadline)
+    }
 }
 
 impl<L, R> Proc for AndThenProc<L, R>
@@ -18,14 +43,35 @@ where
 {
     type Output = R::Output;
 
-    fn join(&mut self) -> anyhow::Result<Self::Output> {
-        self.left.join().and(self.right.join())
+    fn join(&mut self) -> ProcResult<Self::Output> {
+        self.join_until(None)
+    }
+
+    fn join_deadline(&mut self, deadline: Instant) -> ProcResult<Self::Output> {
+        self.join_until(Some(deadline))
+    }
+
+    fn status(&self) -> ProcStatus {
+        if self.left_joined {
+            return self.right.status();
+        }
+        match (self.left.status(), self.right.status()) {
+            (ProcStatus::Forgotten, _) | (_, ProcStatus::Forgotten) => ProcStatus::Forgotten,
+            (ProcStatus::Finished, right) => right,
+            (left, _) => left,
+        }
     }
 
     fn forget(&mut self) {
         self.left.forget();
         self.right.forget();
     }
+
+    fn set_drop_policy(&mut self, policy: DropPolicy) {
+        self.drop_policy = policy;
+        self.left.set_drop_policy(policy);
+        self.right.set_drop_policy(policy);
+    }
 }
 
 impl<L, R> Drop for AndThenProc<L, R>
@@ -34,18 +80,19 @@ where
     R: Proc + Send,
 {
     fn drop(&mut self) {
-        let _ = self.join();
-        self.forget();
+        self.drop_policy.apply(self);
     }
 }
 
 #[cfg(test)]
 mod test {
-    use crate::blocking;
-    use crate::proc::Proc;
+    use crate::proc::{Proc, ProcStatus};
     use crate::proc_ext::ProcExt;
+    use crate::{blocking, thread};
     use std::sync::atomic::{AtomicU64, Ordering};
     use std::sync::Arc;
+    use std::task::Poll;
+    use std::time::Duration;
 
     fn add_to_counter(counter: Arc<AtomicU64>, val: u64) -> impl Proc {
         blocking(move || Ok(counter.fetch_add(val, Ordering::SeqCst)))
@@ -70,6 +117,41 @@ mod test {
         assert_eq!(counter.load(Ordering::Relaxed), 0);
     }
 
+    #[test]
+    fn join_timeout() {
+        let (tx, rx) = flume::bounded::<()>(0);
+        let mut joined = thread(move || Ok(rx.recv()?)).and_then(blocking(|| Ok(2)));
+        assert!(joined
+            .join_timeout(Duration::from_millis(10))
+            .unwrap_err()
+            .is_timeout());
+        drop(tx);
+        assert!(joined.join().unwrap_err().user_error().is_some());
+
+        let (tx, rx) = flume::bounded::<()>(0);
+        let mut joined = thread(move || Ok(rx.recv()?)).and_then(blocking(|| Ok(2)));
+        assert!(joined
+            .join_timeout(Duration::from_millis(10))
+            .unwrap_err()
+            .is_timeout());
+        tx.send(()).unwrap();
+        assert_eq!(joined.join().expect("could not join"), 2);
+    }
+
+    #[test]
+    fn try_join() {
+        let (tx, rx) = flume::bounded::<()>(0);
+        let mut joined = thread(move || Ok(rx.recv()?)).and_then(thread(|| Ok(2)));
+        assert!(joined.try_join().is_pending());
+        assert_eq!(joined.status(), ProcStatus::Running);
+        tx.send(()).unwrap();
+        while !joined.is_finished() {
+            std::thread::yield_now();
+        }
+        assert!(matches!(joined.try_join(), Poll::Ready(Ok(2))));
+        assert_eq!(joined.status(), ProcStatus::Joined);
+    }
+
     #[test]
     fn nested() {
         let counter = Arc::new(AtomicU64::new(0));
@@ -79,6 +161,7 @@ mod test {
             add_to_counter(counter.clone(), 4)
                 .and_then(add_to_counter(counter.clone(), 8))
                 .join()
+                .map_err(anyhow::Error::from)
         });
         let joined = left.and_then(middle).and_then(right);
         drop(joined);
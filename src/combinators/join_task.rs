@@ -1,24 +1,33 @@
-use crate::proc::Proc;
+use crate::drop_policy::DropPolicy;
+use crate::error::{ProcError, ProcResult};
+use crate::proc::{Proc, ProcStatus};
 use crate::runners::runtime::TaskRuntime;
-use futures::future::JoinAll;
+use crate::runners::{Spawner, TaskHandle};
+use flume::Receiver;
+use futures::future::AbortHandle;
+use futures::future::BoxFuture;
 use std::future::IntoFuture;
-use tokio::runtime::Handle;
-use tokio::task::{JoinError, JoinHandle};
+use std::time::Instant;
 
 pub struct JoinTasks<T: Send + 'static> {
     runtime: TaskRuntime,
-    tasks: Vec<JoinHandle<T>>,
+    tasks: Vec<TaskHandle<T>>,
+    aborts: Vec<AbortHandle>,
+    pending: Option<Receiver<ProcResult<Vec<T>>>>,
+    settled: Option<ProcStatus>,
+    drop_policy: DropPolicy,
 }
 
 impl<T: Send + 'static> Default for JoinTasks<T> {
     #[inline]
     fn default() -> Self {
-        let runtime = Handle::try_current()
-            .map(TaskRuntime::Entered)
-            .unwrap_or_else(|_| TaskRuntime::new());
         Self {
-            runtime,
+            runtime: TaskRuntime::current_or_global(),
             tasks: Default::default(),
+            aborts: Default::default(),
+            pending: None,
+            settled: None,
+            drop_policy: DropPolicy::default(),
         }
     }
 }
@@ -29,11 +38,27 @@ impl<T: Send + 'static> JoinTasks<T> {
         Self::default()
     }
 
+    #[cfg(feature = "tokio")]
+    #[inline]
+    pub fn with_runtime(handle: tokio::runtime::Handle) -> Self {
+        Self::with_spawner(crate::runners::TokioSpawner::new(handle))
+    }
+
+    /// Spawns the tasks onto an existing executor
+    #[inline]
+    pub fn with_spawner(spawner: impl Spawner + 'static) -> Self {
+        Self::with_task_runtime(TaskRuntime::with_spawner(spawner))
+    }
+
     #[inline]
-    pub fn with_runtime(handle: Handle) -> Self {
+    pub(crate) fn with_task_runtime(runtime: TaskRuntime) -> Self {
         Self {
-            runtime: TaskRuntime::Entered(handle),
+            runtime,
             tasks: Default::default(),
+            aborts: Default::default(),
+            pending: None,
+            settled: None,
+            drop_policy: DropPolicy::default(),
         }
     }
 
@@ -43,53 +68,126 @@ impl<T: Send + 'static> JoinTasks<T> {
         F: IntoFuture<Output = T>,
         <F as IntoFuture>::IntoFuture: Send + 'static,
     {
-        self.tasks
-            .push(self.runtime.handle().spawn(fut.into_future()));
+        let task = self.runtime.spawn(fut.into_future());
+        self.aborts.push(task.abort_handle());
+        self.tasks.push(task);
         self
     }
+
+    /// Awaits all tasks in the background, unless this was already started by a previous join
+    fn pending(&mut self) -> &Receiver<ProcResult<Vec<T>>> {
+        let tasks = &mut self.tasks;
+        let runtime = &self.runtime;
+        self.pending.get_or_insert_with(|| {
+            let tasks = std::mem::take(tasks);
+            let (output_tx, output_rx) = flume::bounded(1);
+            runtime.spawn(async move {
+                let res = futures::future::join_all(tasks)
+                    .await
+                    .into_iter()
+                    .collect::<ProcResult<Vec<_>>>();
+                let _ = output_tx.send_async(res).await;
+            });
+            output_rx
+        })
+    }
+
+    #[inline]
+    fn is_empty(&self) -> bool {
+        self.tasks.is_empty() && self.pending.is_none()
+    }
+
+    fn finish(&mut self, output: ProcResult<Vec<T>>) -> ProcResult<Vec<T>> {
+        self.pending = None;
+        self.aborts.clear();
+        self.settled = Some(ProcStatus::Joined);
+        output
+    }
 }
 
 impl<T: Send + 'static> IntoFuture for JoinTasks<T> {
-    type Output = Vec<Result<T, JoinError>>;
-    type IntoFuture = JoinAll<JoinHandle<T>>;
+    type Output = Vec<ProcResult<T>>;
+    type IntoFuture = BoxFuture<'static, Self::Output>;
 
+    /// Note: A dedicated runtime is kept alive until all tasks completed
     fn into_future(mut self) -> Self::IntoFuture {
+        self.aborts.clear();
         let tasks = std::mem::take(&mut self.tasks);
-        futures::future::join_all(tasks)
+        let mut runtime = self.runtime.take_owned();
+        Box::pin(async move {
+            let output = futures::future::join_all(tasks).await;
+            runtime.shutdown();
+            output
+        })
     }
 }
 
 impl<T: Send + 'static> Proc for JoinTasks<T> {
     type Output = Vec<T>;
 
-    fn join(&mut self) -> anyhow::Result<Self::Output> {
-        if self.tasks.is_empty() {
-            return Ok(Vec::new());
+    fn join(&mut self) -> ProcResult<Self::Output> {
+        if self.settled.is_some() {
+            return Err(ProcError::AlreadyJoined);
+        }
+        if self.is_empty() {
+            return self.finish(Ok(Vec::new()));
+        }
+        let pending = self.pending().clone();
+        let output = self.runtime.block_in_place(|| pending.recv())?;
+        self.finish(output.map_err(ProcError::from).and_then(|output| output))
+    }
+
+    fn join_deadline(&mut self, deadline: Instant) -> ProcResult<Self::Output> {
+        if self.settled.is_some() {
+            return Err(ProcError::AlreadyJoined);
+        }
+        if self.is_empty() {
+            return self.finish(Ok(Vec::new()));
+        }
+        let pending = self.pending().clone();
+        // Only wait on the runtime when actually blocking, so polling never fails
+        let recv = || pending.recv_deadline(deadline);
+        let output = if deadline > Instant::now() {
+            self.runtime.block_in_place(recv)?
+        } else {
+            recv()
+        };
+        let output = match output {
+            Err(flume::RecvTimeoutError::Timeout) => return Err(ProcError::TimedOut),
+            output => output.map_err(ProcError::from),
+        };
+        self.finish(output.and_then(|output| output))
+    }
+
+    fn status(&self) -> ProcStatus {
+        match (&self.pending, self.settled) {
+            (None, Some(settled)) if self.tasks.is_empty() => settled,
+            (Some(pending), _) if pending.is_empty() => ProcStatus::Running,
+            (Some(_), _) => ProcStatus::Finished,
+            (None, _) if self.tasks.iter().all(TaskHandle::is_finished) => ProcStatus::Finished,
+            (None, _) => ProcStatus::Running,
         }
-        let tasks = std::mem::take(&mut self.tasks);
-        let (output_tx, output_rx) = flume::bounded(1);
-        self.runtime.handle().spawn(async move {
-            let res = futures::future::join_all(tasks)
-                .await
-                .into_iter()
-                .map(|res| res.map_err(anyhow::Error::from))
-                .collect::<anyhow::Result<Vec<_>>>();
-            let _ = output_tx.send_async(res).await;
-        });
-        output_rx.recv()?
     }
 
     #[inline]
     fn forget(&mut self) {
-        for task in self.tasks.drain(..) {
+        for task in self.aborts.drain(..) {
             task.abort();
         }
+        self.tasks.clear();
+        self.pending = None;
+        self.settled = Some(ProcStatus::Forgotten);
+    }
+
+    /// Note: Detached tasks keep running, unless they were spawned onto a dedicated runtime
+    fn set_drop_policy(&mut self, policy: DropPolicy) {
+        self.drop_policy = policy;
     }
 }
 
 impl<T: Send + 'static> Drop for JoinTasks<T> {
     fn drop(&mut self) {
-        let _ = self.join();
+        self.drop_policy.apply(self);
         self.runtime.shutdown();
     }
 }
@@ -97,7 +195,8 @@ impl<T: Send + 'static> Drop for JoinTasks<T> {
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::tokio;
+    use crate::proc_ext::ProcExt;
+    use std::task::Poll;
     use std::time::Duration;
 
     #[test]
@@ -121,12 +220,59 @@ mod test {
         assert_eq!(results, vec![1, 2, 3])
     }
 
+    #[test]
+    fn join_timeout() {
+        let (tx, rx) = flume::bounded::<()>(0);
+        let mut tasks = JoinTasks::new().and(async move { 1 }).and(async move {
+            let _ = rx.recv_async().await;
+            2
+        });
+        assert!(matches!(
+            tasks.join_timeout(Duration::from_millis(1)),
+            Err(ProcError::TimedOut)
+        ));
+        tx.send(()).unwrap();
+        assert_eq!(tasks.join().expect("could not join"), vec![1, 2]);
+        assert_eq!(tasks.status(), ProcStatus::Joined);
+    }
+
+    #[test]
+    fn try_join() {
+        let (tx, rx) = flume::bounded::<()>(0);
+        let mut tasks = JoinTasks::new().and(async move { rx.recv_async().await.is_ok() });
+        assert!(tasks.try_join().is_pending());
+        assert_eq!(tasks.status(), ProcStatus::Running);
+        tx.send(()).unwrap();
+        while !tasks.is_finished() {
+            std::thread::yield_now();
+        }
+        assert!(matches!(tasks.try_join(), Poll::Ready(Ok(output)) if output == vec![true]));
+    }
+
+    #[test]
+    fn join_twice() {
+        let mut tasks = JoinTasks::new().and(async move { 1 });
+        assert_eq!(tasks.join().expect("could not join"), vec![1]);
+        assert!(matches!(tasks.join(), Err(ProcError::AlreadyJoined)));
+        assert!(matches!(
+            tasks.join_timeout(Duration::from_millis(1)),
+            Err(ProcError::AlreadyJoined)
+        ));
+    }
+
     #[test]
     fn join_0() {
         let results = JoinTasks::<()>::new().join().expect("could not join");
         assert_eq!(results, vec![])
     }
 
+    #[test]
+    fn join_0_panic_if_unjoined() {
+        let mut tasks = JoinTasks::<()>::new().on_drop(DropPolicy::PanicIfUnjoined);
+        tasks.join().expect("could not join");
+        assert_eq!(tasks.status(), ProcStatus::Joined);
+    }
+
     #[tokio::test]
     async fn into_future() {
         let tasks = JoinTasks::new().and(async move { 1 }).and(async move { 2 });
@@ -138,9 +284,17 @@ mod test {
         assert_eq!(results, vec![1, 2])
     }
 
+    #[test]
+    fn join_async() {
+        let tasks = JoinTasks::new().and(async move { 1 }).and(async move { 2 });
+        let results = futures::executor::block_on(tasks.join_async());
+        assert_eq!(results.expect("could not join"), vec![1, 2]);
+    }
+
+    #[cfg(feature = "tokio")]
     #[test]
     fn inside_proc() {
-        tokio(async {
+        crate::tokio(async {
             tokio::task::spawn_blocking(|| {
                 let results = JoinTasks::new()
                     .and(async move { 1 })
@@ -156,9 +310,7 @@ mod test {
         .expect("Could not join")
     }
 
-    /// Note: This is generally a terrible idea as this will block one of the executor threads
-    /// But it's functional..
-    /// If spawned inside a single-threaded runtime, it will block the runtime
+    /// Note: The executor thread is handed off while joining, but awaiting is still preferred
     #[tokio::test(flavor = "multi_thread")]
     async fn inside_multi_threaded_runtime() {
         tokio::time::timeout(Duration::from_secs(5), async {
@@ -171,4 +323,74 @@ mod test {
         .await
         .expect("timed out");
     }
+
+    #[tokio::test]
+    async fn inside_current_thread_runtime() {
+        let results = JoinTasks::new()
+            .and(async move { 1 })
+            .and(async move { 2 })
+            .join()
+            .expect("could not join");
+        assert_eq!(results, vec![1, 2]);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn inside_spawn_blocking() {
+        let handle = tokio::runtime::Handle::current();
+        let results = tokio::task::spawn_blocking(move || {
+            JoinTasks::with_runtime(handle).and(async move { 1 }).join()
+        })
+        .await
+        .expect("could not join");
+        assert_eq!(results.expect("could not join"), vec![1]);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn inside_spawn_blocking_busy_driver() {
+        let handle = tokio::runtime::Handle::current();
+        let results = tokio::task::spawn_blocking(move || {
+            JoinTasks::with_runtime(handle).and(async move { 1 }).join()
+        });
+        // The driver only gets to the task once it is done blocking itself
+        std::thread::sleep(Duration::from_millis(200));
+        let results = results.await.expect("could not join");
+        assert_eq!(results.expect("could not join"), vec![1]);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn would_deadlock_in_task() {
+        let handle = tokio::runtime::Handle::current();
+        let output = JoinTasks::with_runtime(handle.clone())
+            .and(async move { JoinTasks::with_runtime(handle).and(async move { 1 }).join() })
+            .join_async()
+            .await
+            .expect("could not join");
+        assert!(matches!(output[..], [Err(ProcError::WouldDeadlock)]));
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn would_deadlock() {
+        let mut tasks =
+            JoinTasks::with_runtime(tokio::runtime::Handle::current()).and(async move { 1 });
+        assert!(matches!(tasks.join(), Err(ProcError::WouldDeadlock)));
+        while !tasks.is_finished() {
+            tokio::task::yield_now().await;
+        }
+        assert!(matches!(tasks.try_join(), Poll::Ready(Ok(output)) if output == vec![1]));
+    }
+
+    #[cfg(feature = "smol")]
+    #[test]
+    fn smol_spawner() {
+        let results = JoinTasks::with_spawner(crate::SmolSpawner::global())
+            .and(async move { 1 })
+            .and(async move { 2 })
+            .join()
+            .expect("could not join");
+        assert_eq!(results, vec![1, 2]);
+    }
 }
@@ -0,0 +1,265 @@
+use crate::drop_policy::DropPolicy;
+use crate::error::{ProcError, ProcResult};
+use crate::panic::catch_unwind;
+use crate::proc::{join_until, Proc, ProcStatus};
+use std::fmt::Display;
+use std::task::Waker;
+use std::time::Instant;
+
+/// Transforms the result of a unit of execution once it completed.
+/// Timeouts are passed through without calling the [`Adapter`]
+pub trait Adapter<T>: Send {
+    type Output: Send;
+    fn adapt(self, output: ProcResult<T>) -> ProcResult<Self::Output>;
+}
+
+/// [`Proc`] combinator that transforms the result of a unit of execution through an [`Adapter`].
+/// Panics of the [`Adapter`] are captured as [`ProcError::Panicked`]
+pub struct AdapterProc<P, A>
+where
+    P: Proc + Send,
+    A: Adapter<P::Output>,
+{
+    pub(crate) proc: P,
+    pub(crate) adapter: Option<A>,
+}
+
+impl<P, A> AdapterProc<P, A>
+where
+    P: Proc + Send,
+    A: Adapter<P::Output>,
+{
+    pub(crate) fn new(proc: P, adapter: A) -> Self {
+        Self {
+            proc,
+            adapter: Some(adapter),
+        }
+    }
+
+    fn join_until(&mut self, deadline: Option<Instant>) -> ProcResult<A::Output> {
+        match join_until(&mut self.proc, deadline) {
+            Err(ProcError::TimedOut) => Err(ProcError::TimedOut),
+            output => match self.adapter.take() {
+                Some(adapter) => catch_unwind(|| adapter.adapt(output))?,
+                None => Err(output.err().unwrap_or(ProcError::AlreadyJoined)),
+            },
+        }
+    }
+}
+
+impl<P, A> Proc for AdapterProc<P, A>
+where
+    P: Proc + Send,
+    A: Adapter<P::Output>,
+{
+    type Output = A::Output;
+
+    fn join(&mut self) -> ProcResult<A::Output> {
+        self.join_until(None)
+    }
+
+    fn join_deadline(&mut self, deadline: Instant) -> ProcResult<A::Output> {
+        self.join_until(Some(deadline))
+    }
+
+    fn status(&self) -> ProcStatus {
+        self.proc.status()
+    }
+
+    fn forget(&mut self) {
+        self.proc.forget();
+    }
+
+    fn set_drop_policy(&mut self, policy: DropPolicy) {
+        self.proc.set_drop_policy(policy);
+    }
+
+    fn register_waker(&mut self, waker: &Waker) -> bool {
+        self.proc.register_waker(waker)
+    }
+}
+
+/// [`Adapter`] that transforms the output of a unit of execution
+pub struct Map<F>(pub(crate) F);
+
+impl<T, U, F> Adapter<T> for Map<F>
+where
+    U: Send,
+    F: FnOnce(T) -> U + Send,
+{
+    type Output = U;
+
+    fn adapt(self, output: ProcResult<T>) -> ProcResult<U> {
+        output.map(self.0)
+    }
+}
+
+/// [`Adapter`] that transforms the output of a unit of execution with a fallible function
+pub struct AndThenResult<F>(pub(crate) F);
+
+impl<T, U, F> Adapter<T> for AndThenResult<F>
+where
+    U: Send,
+    F: FnOnce(T) -> anyhow::Result<U> + Send,
+{
+    type Output = U;
+
+    fn adapt(self, output: ProcResult<T>) -> ProcResult<U> {
+        self.0(output?).map_err(ProcError::from)
+    }
+}
+
+/// [`Adapter`] that transforms the error of a unit of execution
+pub struct MapErr<F>(pub(crate) F);
+
+impl<T: Send, F> Adapter<T> for MapErr<F>
+where
+    F: FnOnce(ProcError) -> ProcError + Send,
+{
+    type Output = T;
+
+    fn adapt(self, output: ProcResult<T>) -> ProcResult<T> {
+        output.map_err(self.0)
+    }
+}
+
+/// [`Adapter`] that calls a function with a reference to the output of a unit of execution
+pub struct Inspect<F>(pub(crate) F);
+
+impl<T: Send, F> Adapter<T> for Inspect<F>
+where
+    F: FnOnce(&T) + Send,
+{
+    type Output = T;
+
+    fn adapt(self, output: ProcResult<T>) -> ProcResult<T> {
+        output.inspect(self.0)
+    }
+}
+
+/// [`Adapter`] that calls a function with a reference to the error of a unit of execution
+pub struct InspectErr<F>(pub(crate) F);
+
+impl<T: Send, F> Adapter<T> for InspectErr<F>
+where
+    F: FnOnce(&ProcError) + Send,
+{
+    type Output = T;
+
+    fn adapt(self, output: ProcResult<T>) -> ProcResult<T> {
+        output.inspect_err(self.0)
+    }
+}
+
+/// [`Adapter`] that adds context to the user error of a unit of execution,
+/// similar to [`anyhow::Context`]. Other errors are left unchanged, so they can still be matched.
+pub struct Context<C>(pub(crate) C);
+
+impl<T: Send, C> Adapter<T> for Context<C>
+where
+    C: Display + Send + Sync + 'static,
+{
+    type Output = T;
+
+    fn adapt(self, output: ProcResult<T>) -> ProcResult<T> {
+        match output {
+            Err(ProcError::User(err)) => Err(ProcError::User(err.context(self.0))),
+            output => output,
+        }
+    }
+}
+
+/// [`Proc`] combinator that transforms the output of a unit of execution
+pub type MapProc<P, F> = AdapterProc<P, Map<F>>;
+
+/// [`Proc`] combinator that transforms the output of a unit of execution with a fallible function
+pub type AndThenResultProc<P, F> = AdapterProc<P, AndThenResult<F>>;
+
+/// [`Proc`] combinator that transforms the error of a unit of execution.
+/// Timeouts are passed through unchanged.
+pub type MapErrProc<P, F> = AdapterProc<P, MapErr<F>>;
+
+/// [`Proc`] combinator that calls a function with a reference to the output of a unit of execution
+pub type InspectProc<P, F> = AdapterProc<P, Inspect<F>>;
+
+/// [`Proc`] combinator that calls a function with a reference to the error of a unit of execution.
+/// Timeouts are not inspected.
+pub type InspectErrProc<P, F> = AdapterProc<P, InspectErr<F>>;
+
+/// [`Proc`] combinator that adds context to the user error of a unit of execution
+pub type ContextProc<P, C> = AdapterProc<P, Context<C>>;
+
+#[cfg(test)]
+mod test {
+    use crate::error::ProcError;
+    use crate::proc::Proc;
+    use crate::proc_ext::ProcExt;
+    use crate::{blocking, thread};
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::Arc;
+
+    fn fail() -> impl Proc<Output = u64> {
+        blocking(|| anyhow::bail!("failed"))
+    }
+
+    #[test]
+    fn map() {
+        let output = thread(|| Ok(2))
+            .map(|val| val * 2)
+            .and_then_result(|val| Ok(u8::try_from(val)?))
+            .join()
+            .expect("could not join");
+        assert_eq!(output, 4u8);
+    }
+
+    #[test]
+    fn map_err() {
+        let err = fail().map_err(|_| ProcError::Cancelled).join().unwrap_err();
+        assert!(err.is_cancelled());
+    }
+
+    #[test]
+    fn inspect() {
+        let counter = Arc::new(AtomicU64::new(0));
+        let (ok, err) = (counter.clone(), counter.clone());
+        let output = blocking(|| Ok(1))
+            .inspect(move |val| {
+                ok.fetch_add(*val, Ordering::SeqCst);
+            })
+            .join()
+            .expect("could not join");
+        assert_eq!(output, 1);
+        let _ = fail()
+            .inspect_err(move |_| {
+                err.fetch_add(2, Ordering::SeqCst);
+            })
+            .join();
+        assert_eq!(counter.load(Ordering::Relaxed), 3);
+    }
+
+    #[test]
+    fn panicked() {
+        let err = blocking(|| Ok(1))
+            .map(|_| -> u64 { panic!("boom") })
+            .join()
+            .unwrap_err();
+        assert!(matches!(err, ProcError::Panicked(panic) if panic.message() == "boom"));
+        let err = fail().inspect_err(|_| panic!("boom")).join().unwrap_err();
+        assert!(err.is_panic());
+    }
+
+    #[test]
+    fn context() {
+        let err = fail().context("while testing").join().unwrap_err();
+        assert_eq!(format!("{err:#}"), "while testing: failed");
+    }
+
+    #[test]
+    fn flatten() {
+        let output = thread(|| Ok(blocking(|| Ok(1))))
+            .flatten()
+            .join()
+            .expect("could not join");
+        assert_eq!(output, 1);
+    }
+}
@@ -1,20 +1,47 @@
 mod and;
+#[cfg(feature = "async")]
 mod join_task;
+pub(crate) mod map;
 mod or;
+mod or_with;
+mod retry;
+#[cfg(feature = "async")]
 mod select_task;
+mod then;
+mod zip;
 
 pub use and::AndThenProc;
+#[cfg(feature = "async")]
 pub use join_task::JoinTasks;
+pub use map::{
+    Adapter, AdapterProc, AndThenResultProc, ContextProc, InspectErrProc, InspectProc, MapErrProc,
+    MapProc,
+};
 pub use or::OrElseProc;
+pub use or_with::OrElseWithProc;
+pub use retry::{Backoff, RetryPolicy, RetryProc};
+#[cfg(feature = "async")]
 pub use select_task::SelectTasks;
+pub use then::{FlattenProc, ThenProc};
+pub use zip::ZipProc;
 
-use crate::proc::Proc;
+use crate::cancel::CancellationToken;
+use crate::drop_policy::DropPolicy;
+use crate::error::{ProcError, ProcResult};
+use crate::panic::catch_unwind;
+use crate::proc::{Proc, ProcStatus};
+use std::time::Instant;
 
 /// Instance of a [`Proc`] which calls a simple function
-pub struct BlockingProc<F, T>(pub(crate) Option<F>)
+pub struct BlockingProc<F, T>
 where
     F: FnOnce() -> anyhow::Result<T> + Send,
-    T: Send;
+    T: Send,
+{
+    pub(crate) f: Option<F>,
+    pub(crate) token: CancellationToken,
+    pub(crate) drop_policy: DropPolicy,
+}
 
 impl<F, T> Proc for BlockingProc<F, T>
 where
@@ -23,16 +50,37 @@ where
 {
     type Output = T;
 
-    fn join(&mut self) -> anyhow::Result<T> {
-        if let Some(f) = self.0.take() {
-            f()
+    fn join(&mut self) -> ProcResult<T> {
+        let f = self.f.take().ok_or(ProcError::AlreadyJoined)?;
+        catch_unwind(f)?.map_err(ProcError::from)
+    }
+
+    /// Runs in the foreground, so it can only time out before it was started
+    fn join_deadline(&mut self, deadline: Instant) -> ProcResult<T> {
+        if self.f.is_some() && Instant::now() >= deadline {
+            return Err(ProcError::TimedOut);
+        }
+        self.join()
+    }
+
+    fn status(&self) -> ProcStatus {
+        if self.f.is_some() {
+            ProcStatus::Pending
+        } else if self.token.is_cancelled() {
+            ProcStatus::Forgotten
         } else {
-            Err(anyhow::Error::msg("Nothing to join"))
+            ProcStatus::Joined
         }
     }
 
     fn forget(&mut self) {
-        self.0.take();
+        if self.f.take().is_some() {
+            self.token.cancel();
+        }
+    }
+
+    fn set_drop_policy(&mut self, policy: DropPolicy) {
+        self.drop_policy = policy;
     }
 }
 
@@ -42,19 +90,37 @@ where
     T: Send,
 {
     fn drop(&mut self) {
-        if self.join().is_err() {}
+        self.drop_policy.apply(self);
     }
 }
 
 /// Instance of a [`Proc`] which returns immediately when called
-pub struct NopProc;
+pub struct NopProc {
+    pub(crate) status: ProcStatus,
+}
 
 impl Proc for NopProc {
     type Output = ();
 
-    fn join(&mut self) -> anyhow::Result<()> {
+    fn join(&mut self) -> ProcResult<()> {
+        if self.status != ProcStatus::Finished {
+            return Err(ProcError::AlreadyJoined);
+        }
+        self.status = ProcStatus::Joined;
         Ok(())
     }
 
-    fn forget(&mut self) {}
+    fn join_deadline(&mut self, _deadline: Instant) -> ProcResult<()> {
+        self.join()
+    }
+
+    fn status(&self) -> ProcStatus {
+        self.status
+    }
+
+    fn forget(&mut self) {
+        if self.status == ProcStatus::Finished {
+            self.status = ProcStatus::Forgotten;
+        }
+    }
 }
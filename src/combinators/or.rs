@@ -1,4 +1,7 @@
-use crate::proc::Proc;
+use crate::drop_policy::DropPolicy;
+use crate::error::{ProcError, ProcResult};
+use crate::proc::{join_until, Proc, ProcStatus};
+use std::time::Instant;
 
 /// [`Proc`] combinator that allows combining the results of two units of execution
 /// sharing the same result types
@@ -9,6 +12,28 @@ where
 {
     pub(crate) left: L,
     pub(crate) right: R,
+    pub(crate) left_failed: bool,
+    pub(crate) drop_policy: DropPolicy,
+}
+
+impl<L, R> OrElseProc<L, R>
+where
+    L: Proc + Send,
+    R: Proc<Output = L::Output> + Send,
+{
+    fn join_until(&mut self, deadline: Option<Instant>) -> ProcResult<R::Output> {
+        if !self.left_failed {
+            match join_until(&mut self.left, deadline) {
+                Ok(output) => {
+                    self.right.forget();
+                    return Ok(output);
+                }
+                Err(ProcError::TimedOut) => return Err(ProcError::TimedOut),
+                Err(_) => self.left_failed = true,
+            }
+        }
+        join_until(&mut self.right, deadline)
+    }
 }
 
 impl<L, R> Proc for OrElseProc<L, R>
@@ -18,14 +43,33 @@ where
 {
     type Output = R::Output;
 
-    fn join(&mut self) -> anyhow::Result<Self::Output> {
-        self.left.join().or_else(|_| self.right.join())
+    fn join(&mut self) -> ProcResult<Self::Output> {
+        self.join_until(None)
+    }
+
+    fn join_deadline(&mut self, deadline: Instant) -> ProcResult<Self::Output> {
+        self.join_until(Some(deadline))
+    }
+
+    /// Reports the left [`Proc`] until it failed, as the fallback might not be needed
+    fn status(&self) -> ProcStatus {
+        if self.left_failed {
+            self.right.status()
+        } else {
+            self.left.status()
+        }
     }
 
     fn forget(&mut self) {
         self.left.forget();
         self.right.forget();
     }
+
+    fn set_drop_policy(&mut self, policy: DropPolicy) {
+        self.drop_policy = policy;
+        self.left.set_drop_policy(policy);
+        self.right.set_drop_policy(policy);
+    }
 }
 
 impl<L, R> Drop for OrElseProc<L, R>
@@ -34,18 +78,18 @@ where
     R: Proc<Output = L::Output> + Send,
 {
     fn drop(&mut self) {
-        let _ = self.join();
-        self.forget();
+        self.drop_policy.apply(self);
     }
 }
 
 #[cfg(test)]
 mod test {
-    use crate::blocking;
-    use crate::proc::Proc;
+    use crate::proc::{Proc, ProcStatus};
     use crate::proc_ext::ProcExt;
+    use crate::{blocking, thread};
     use std::sync::atomic::{AtomicU64, Ordering};
     use std::sync::Arc;
+    use std::task::Poll;
 
     fn add_to_counter(counter: Arc<AtomicU64>, val: u64) -> impl Proc {
         blocking(move || Ok(counter.fetch_add(val, Ordering::SeqCst)))
@@ -70,6 +114,29 @@ mod test {
         assert_eq!(counter.load(Ordering::Relaxed), 0);
     }
 
+    #[test]
+    fn try_join() {
+        let (tx, rx) = flume::bounded::<u64>(0);
+        let (fallback_tx, fallback_rx) = flume::bounded::<u64>(0);
+        let mut joined =
+            thread(move || Ok(rx.recv()?)).or_else(thread(move || Ok(fallback_rx.recv()?)));
+        assert!(joined.try_join().is_pending());
+        assert_eq!(joined.status(), ProcStatus::Running);
+        // The left proc fails, so the fallback is reported from then on
+        drop(tx);
+        while !joined.is_finished() {
+            std::thread::yield_now();
+        }
+        assert!(joined.try_join().is_pending());
+        assert_eq!(joined.status(), ProcStatus::Running);
+        fallback_tx.send(2).unwrap();
+        while !joined.is_finished() {
+            std::thread::yield_now();
+        }
+        assert!(matches!(joined.try_join(), Poll::Ready(Ok(2))));
+        assert_eq!(joined.status(), ProcStatus::Joined);
+    }
+
     #[test]
     fn nested() {
         let counter = Arc::new(AtomicU64::new(0));
@@ -79,6 +146,7 @@ mod test {
             add_to_counter(counter.clone(), 4)
                 .and_then(add_to_counter(counter.clone(), 8))
                 .join()
+                .map_err(anyhow::Error::from)
         });
         let joined = left.or_else(middle).or_else(right);
         drop(joined);
@@ -0,0 +1,169 @@
+use crate::drop_policy::DropPolicy;
+use crate::error::{ProcError, ProcResult};
+use crate::panic::catch_unwind;
+use crate::proc::{join_until, Proc, ProcStatus};
+use std::time::Instant;
+
+/// [`Proc`] combinator that lazily creates a fallback unit of execution from the error of the first
+pub struct OrElseWithProc<L, F, R>
+where
+    L: Proc + Send,
+    F: FnOnce(&ProcError) -> R + Send,
+    R: Proc<Output = L::Output> + Send,
+{
+    pub(crate) left: L,
+    pub(crate) f: Option<F>,
+    pub(crate) right: Option<R>,
+    pub(crate) primary: Option<ProcError>,
+    pub(crate) drop_policy: Option<DropPolicy>,
+}
+
+impl<L, F, R> OrElseWithProc<L, F, R>
+where
+    L: Proc + Send,
+    F: FnOnce(&ProcError) -> R + Send,
+    R: Proc<Output = L::Output> + Send,
+{
+    fn join_until(&mut self, deadline: Option<Instant>) -> ProcResult<R::Output> {
+        if self.right.is_none() {
+            let Some(f) = self.f.take() else {
+                return Err(ProcError::AlreadyJoined);
+            };
+            match join_until(&mut self.left, deadline) {
+                Ok(output) => return Ok(output),
+                Err(ProcError::TimedOut) => {
+                    self.f = Some(f);
+                    return Err(ProcError::TimedOut);
+                }
+                Err(err) => {
+                    let right = catch_unwind(|| f(&err));
+                    self.primary = Some(err);
+                    let mut right = right?;
+                    if let Some(policy) = self.drop_policy {
+                        right.set_drop_policy(policy);
+                    }
+                    self.right = Some(right);
+                }
+            }
+        }
+        let Some(right) = &mut self.right else {
+            return Err(ProcError::AlreadyJoined);
+        };
+        match join_until(right, deadline) {
+            Err(ProcError::TimedOut) => Err(ProcError::TimedOut),
+            Err(fallback) => Err(match self.primary.take() {
+                Some(primary) => ProcError::Fallback {
+                    primary: Box::new(primary),
+                    fallback: Box::new(fallback),
+                },
+                None => fallback,
+            }),
+            Ok(output) => {
+                self.primary = None;
+                Ok(output)
+            }
+        }
+    }
+}
+
+impl<L, F, R> Proc for OrElseWithProc<L, F, R>
+where
+    L: Proc + Send,
+    F: FnOnce(&ProcError) -> R + Send,
+    R: Proc<Output = L::Output> + Send,
+{
+    type Output = R::Output;
+
+    fn join(&mut self) -> ProcResult<Self::Output> {
+        self.join_until(None)
+    }
+
+    fn join_deadline(&mut self, deadline: Instant) -> ProcResult<Self::Output> {
+        self.join_until(Some(deadline))
+    }
+
+    fn status(&self) -> ProcStatus {
+        match &self.right {
+            Some(right) => right.status(),
+            None => self.left.status(),
+        }
+    }
+
+    fn forget(&mut self) {
+        self.left.forget();
+        self.f = None;
+        if let Some(right) = &mut self.right {
+            right.forget();
+        }
+    }
+
+    fn set_drop_policy(&mut self, policy: DropPolicy) {
+        self.drop_policy = Some(policy);
+        self.left.set_drop_policy(policy);
+        if let Some(right) = &mut self.right {
+            right.set_drop_policy(policy);
+        }
+    }
+}
+
+impl<L, F, R> Drop for OrElseWithProc<L, F, R>
+where
+    L: Proc + Send,
+    F: FnOnce(&ProcError) -> R + Send,
+    R: Proc<Output = L::Output> + Send,
+{
+    fn drop(&mut self) {
+        self.drop_policy.unwrap_or_default().apply(self);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::error::ProcError;
+    use crate::proc::Proc;
+    use crate::proc_ext::ProcExt;
+    use crate::{blocking, thread};
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::Arc;
+
+    #[test]
+    fn receives_error() {
+        let output = thread(|| -> anyhow::Result<String> { anyhow::bail!("primary") })
+            .or_else_with(|err| {
+                let reason = err.to_string();
+                blocking(move || Ok(format!("recovered from {reason}")))
+            })
+            .join()
+            .expect("could not join");
+        assert_eq!(output, "recovered from primary");
+    }
+
+    #[test]
+    fn lazy_on_success() {
+        let counter = Arc::new(AtomicU64::new(0));
+        let created = counter.clone();
+        let output = blocking(|| Ok(1))
+            .or_else_with(move |_| {
+                created.fetch_add(1, Ordering::SeqCst);
+                blocking(|| Ok(2))
+            })
+            .join()
+            .expect("could not join");
+        assert_eq!(output, 1);
+        assert_eq!(counter.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn aggregates_errors() {
+        let err = blocking(|| -> anyhow::Result<()> { anyhow::bail!("primary") })
+            .or_else_with(|_| blocking(|| anyhow::bail!("fallback")))
+            .join()
+            .unwrap_err();
+        assert!(matches!(
+            &err,
+            ProcError::Fallback { primary, fallback }
+                if primary.to_string() == "primary" && fallback.to_string() == "fallback"
+        ));
+        assert_eq!(err.to_string(), "fallback (primary failed: primary)");
+    }
+}
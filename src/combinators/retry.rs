@@ -0,0 +1,377 @@
+use crate::drop_policy::DropPolicy;
+use crate::error::{ProcError, ProcResult};
+use crate::panic::catch_unwind;
+use crate::proc::{join_until, Proc, ProcStatus};
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// Number of attempts after which a [`RetryPolicy`] gives up, unless configured otherwise
+const DEFAULT_MAX_ATTEMPTS: usize = 10;
+
+/// Delay between consecutive attempts of a [`RetryProc`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Backoff {
+    /// Waits the same delay before every retry
+    Fixed(Duration),
+    /// Multiplies the delay by `factor` after every retry, up to `max`.
+    /// The factor must not be negative or NaN
+    Exponential {
+        initial: Duration,
+        factor: f64,
+        max: Duration,
+    },
+}
+
+/// Determines when and how often a [`RetryProc`] re-instantiates a failed [`Proc`]
+pub struct RetryPolicy {
+    backoff: Backoff,
+    // State of the xorshift generator randomizing the delays, if enabled
+    jitter: Option<AtomicU64>,
+    max_attempts: usize,
+    max_elapsed: Option<Duration>,
+    retry_if: Box<dyn Fn(&ProcError) -> bool + Send + Sync>,
+}
+
+impl RetryPolicy {
+    /// Panics if the factor of an exponential backoff is negative or NaN
+    pub fn new(backoff: Backoff) -> Self {
+        if let Backoff::Exponential { factor, .. } = backoff {
+            assert!(factor >= 0.0, "invalid backoff factor: {factor}");
+        }
+        Self {
+            backoff,
+            jitter: None,
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+            max_elapsed: None,
+            retry_if: Box::new(|err| !err.is_cancelled()),
+        }
+    }
+
+    /// Retries after a constant delay
+    #[inline]
+    pub fn fixed(delay: Duration) -> Self {
+        Self::new(Backoff::Fixed(delay))
+    }
+
+    /// Retries after a delay which doubles after every attempt, up to `max`
+    #[inline]
+    pub fn exponential(initial: Duration, max: Duration) -> Self {
+        Self::new(Backoff::Exponential {
+            initial,
+            factor: 2.0,
+            max,
+        })
+    }
+
+    /// Randomizes each delay between half and all of its value,
+    /// to avoid retrying many units of execution in lockstep
+    pub fn jitter(mut self) -> Self {
+        // Seeded once, xorshift requires a non-zero state
+        let seed = RandomState::new().build_hasher().finish() | 1;
+        self.jitter = Some(AtomicU64::new(seed));
+        self
+    }
+
+    /// Gives up after `attempts` attempts, including the first one, which defaults to 10
+    pub fn max_attempts(mut self, attempts: usize) -> Self {
+        self.max_attempts = attempts;
+        self
+    }
+
+    /// Gives up once no retry can be started within `elapsed` since the first attempt
+    pub fn max_elapsed(mut self, elapsed: Duration) -> Self {
+        self.max_elapsed = Some(elapsed);
+        self
+    }
+
+    /// Only retries errors matching the predicate.
+    /// By default all errors are retried, except for [`ProcError::Cancelled`]
+    pub fn retry_if<F>(mut self, predicate: F) -> Self
+    where
+        F: Fn(&ProcError) -> bool + Send + Sync + 'static,
+    {
+        self.retry_if = Box::new(predicate);
+        self
+    }
+
+    /// Delay before the next attempt, after `attempt` attempts failed
+    pub(crate) fn delay(&self, attempt: usize) -> Duration {
+        let delay = match self.backoff {
+            Backoff::Fixed(delay) => delay,
+            Backoff::Exponential {
+                initial,
+                factor,
+                max,
+            } => {
+                let exponent = attempt.saturating_sub(1).min(i32::MAX as usize) as i32;
+                // Clamped before converting, as the delay overflows a `Duration` quickly
+                let delay = initial.as_secs_f64() * factor.powi(exponent);
+                Duration::try_from_secs_f64(delay.min(max.as_secs_f64())).unwrap_or(max)
+            }
+        };
+        match &self.jitter {
+            Some(state) => {
+                let random = next_random(state);
+                delay / 2 + delay.mul_f64((random as f64 / u64::MAX as f64) / 2.0)
+            }
+            None => delay,
+        }
+    }
+
+    /// Returns the time of the next attempt, if the error should be retried
+    fn next_attempt(&self, err: &ProcError, attempt: usize, started: Instant) -> Option<Instant> {
+        if attempt >= self.max_attempts || !(self.retry_if)(err) {
+            return None;
+        }
+        let next = Instant::now() + self.delay(attempt);
+        match self.max_elapsed {
+            Some(max) if next.duration_since(started) > max => None,
+            _ => Some(next),
+        }
+    }
+}
+
+/// Advances the xorshift64* generator, returning the next random number
+fn next_random(state: &AtomicU64) -> u64 {
+    let step = |mut x: u64| {
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        x
+    };
+    let previous = state
+        .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |x| Some(step(x)))
+        .unwrap_or_else(|x| x);
+    step(previous).wrapping_mul(0x2545_f491_4f6c_dd1d)
+}
+
+/// [`Proc`] which re-instantiates a unit of execution from a factory until it succeeds
+/// or the [`RetryPolicy`] gives up. Attempts are only started while joining.
+pub struct RetryProc<F, P>
+where
+    F: FnMut() -> P + Send,
+    P: Proc + Send,
+{
+    pub(crate) factory: F,
+    pub(crate) policy: RetryPolicy,
+    pub(crate) current: Option<P>,
+    pub(crate) attempts: usize,
+    pub(crate) started: Option<Instant>,
+    pub(crate) retry_at: Option<Instant>,
+    pub(crate) settled: Option<ProcStatus>,
+    pub(crate) drop_policy: Option<DropPolicy>,
+}
+
+impl<F, P> RetryProc<F, P>
+where
+    F: FnMut() -> P + Send,
+    P: Proc + Send,
+{
+    /// Number of attempts started so far
+    #[inline]
+    pub fn attempts(&self) -> usize {
+        self.attempts
+    }
+
+    fn join_until(&mut self, deadline: Option<Instant>) -> ProcResult<P::Output> {
+        if self.settled.is_some() {
+            return Err(ProcError::AlreadyJoined);
+        }
+        let started = *self.started.get_or_insert_with(Instant::now);
+        loop {
+            if let Some(retry_at) = self.retry_at {
+                let wake = deadline.map_or(retry_at, |deadline| deadline.min(retry_at));
+                std::thread::sleep(wake.saturating_duration_since(Instant::now()));
+                if wake < retry_at {
+                    return Err(ProcError::TimedOut);
+                }
+                self.retry_at = None;
+            }
+
+            let current = match &mut self.current {
+                Some(current) => current,
+                None => {
+                    self.attempts += 1;
+                    let mut current = catch_unwind(&mut self.factory).map_err(|panic| {
+                        self.settled = Some(ProcStatus::Joined);
+                        ProcError::from(panic)
+                    })?;
+                    if let Some(policy) = self.drop_policy {
+                        current.set_drop_policy(policy);
+                    }
+                    self.current.insert(current)
+                }
+            };
+
+            match join_until(current, deadline) {
+                Err(ProcError::TimedOut) => return Err(ProcError::TimedOut),
+                Ok(output) => {
+                    self.current = None;
+                    self.settled = Some(ProcStatus::Joined);
+                    return Ok(output);
+                }
+                Err(err) => {
+                    self.current = None;
+                    match self.policy.next_attempt(&err, self.attempts, started) {
+                        Some(retry_at) => self.retry_at = Some(retry_at),
+                        None => {
+                            self.settled = Some(ProcStatus::Joined);
+                            return Err(err);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl<F, P> Proc for RetryProc<F, P>
+where
+    F: FnMut() -> P + Send,
+    P: Proc + Send,
+{
+    type Output = P::Output;
+
+    fn join(&mut self) -> ProcResult<Self::Output> {
+        self.join_until(None)
+    }
+
+    fn join_deadline(&mut self, deadline: Instant) -> ProcResult<Self::Output> {
+        self.join_until(Some(deadline))
+    }
+
+    fn status(&self) -> ProcStatus {
+        match (&self.settled, &self.current) {
+            (Some(settled), _) => *settled,
+            (None, Some(current)) => current.status(),
+            (None, None) => ProcStatus::Pending,
+        }
+    }
+
+    fn forget(&mut self) {
+        if let Some(current) = &mut self.current {
+            current.forget();
+        }
+        if self.settled.is_none() {
+            self.settled = Some(ProcStatus::Forgotten);
+        }
+    }
+
+    fn set_drop_policy(&mut self, policy: DropPolicy) {
+        self.drop_policy = Some(policy);
+        if let Some(current) = &mut self.current {
+            current.set_drop_policy(policy);
+        }
+    }
+}
+
+impl<F, P> Drop for RetryProc<F, P>
+where
+    F: FnMut() -> P + Send,
+    P: Proc + Send,
+{
+    fn drop(&mut self) {
+        self.drop_policy.unwrap_or_default().apply(self);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::proc_ext::ProcExt;
+    use crate::{blocking, retry};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    fn fail_times(failures: usize) -> impl FnMut() -> Box<dyn Proc<Output = usize>> {
+        let attempts = Arc::new(AtomicUsize::new(0));
+        move || {
+            let attempts = attempts.clone();
+            blocking(move || {
+                let attempt = attempts.fetch_add(1, Ordering::SeqCst) + 1;
+                anyhow::ensure!(attempt > failures, "attempt {attempt} failed");
+                Ok(attempt)
+            })
+            .boxed()
+        }
+    }
+
+    #[test]
+    fn retries_until_success() {
+        let mut proc = retry(fail_times(2), RetryPolicy::fixed(Duration::from_millis(1)));
+        assert_eq!(proc.join().expect("could not join"), 3);
+        assert_eq!(proc.attempts(), 3);
+    }
+
+    #[test]
+    fn max_attempts() {
+        let policy = RetryPolicy::fixed(Duration::from_millis(1)).max_attempts(2);
+        let mut proc = retry(fail_times(5), policy);
+        assert_eq!(proc.join().unwrap_err().to_string(), "attempt 2 failed");
+        assert_eq!(proc.status(), ProcStatus::Joined);
+    }
+
+    #[test]
+    fn default_max_attempts() {
+        let mut proc = retry(fail_times(usize::MAX), RetryPolicy::fixed(Duration::ZERO));
+        assert!(proc.join().is_err());
+        assert_eq!(proc.attempts(), DEFAULT_MAX_ATTEMPTS);
+    }
+
+    #[test]
+    fn retry_if() {
+        let policy = RetryPolicy::fixed(Duration::from_millis(1)).retry_if(ProcError::is_panic);
+        let mut proc = retry(fail_times(5), policy);
+        assert!(proc.join().is_err());
+        assert_eq!(proc.attempts(), 1);
+    }
+
+    #[test]
+    fn join_timeout() {
+        let mut proc = retry(fail_times(1), RetryPolicy::fixed(Duration::from_millis(50)));
+        assert!(proc
+            .join_timeout(Duration::from_millis(5))
+            .unwrap_err()
+            .is_timeout());
+        assert_eq!(proc.join().expect("could not join"), 2);
+    }
+
+    #[test]
+    fn exponential_backoff() {
+        let policy = RetryPolicy::exponential(Duration::from_millis(10), Duration::from_millis(50));
+        let delays = (1..=4)
+            .map(|attempt| policy.delay(attempt))
+            .collect::<Vec<_>>();
+        assert_eq!(delays, [10, 20, 40, 50].map(Duration::from_millis).to_vec());
+
+        let policy = policy.jitter();
+        assert!((1..=4).all(|attempt| {
+            let delay = policy.delay(attempt);
+            delay >= delays[attempt - 1] / 2 && delay <= delays[attempt - 1]
+        }));
+        let jittered = (0..8).map(|_| policy.delay(4)).collect::<Vec<_>>();
+        assert!(jittered.iter().any(|delay| *delay != jittered[0]));
+    }
+
+    #[test]
+    fn exponential_backoff_overflow() {
+        let policy = RetryPolicy::exponential(Duration::from_nanos(1), Duration::from_nanos(10))
+            .max_attempts(2000);
+        assert_eq!(policy.delay(2000), Duration::from_nanos(10));
+        let mut proc = retry(fail_times(usize::MAX), policy);
+        assert!(proc.join().is_err());
+        assert_eq!(proc.attempts(), 2000);
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid backoff factor")]
+    fn invalid_backoff_factor() {
+        RetryPolicy::new(Backoff::Exponential {
+            initial: Duration::from_millis(1),
+            factor: f64::NAN,
+            max: Duration::from_millis(10),
+        });
+    }
+}
@@ -1,23 +1,34 @@
-use crate::proc::Proc;
-use crate::runtime::TaskRuntime;
+use crate::drop_policy::DropPolicy;
+use crate::error::{ProcError, ProcResult};
+use crate::proc::{Proc, ProcStatus};
+use crate::runners::runtime::TaskRuntime;
+use crate::runners::{Spawner, TaskHandle};
+use flume::Receiver;
+use futures::future::AbortHandle;
 use std::future::IntoFuture;
-use tokio::runtime::Handle;
-use tokio::task::JoinHandle;
+use std::time::Instant;
+
+type Selected<T> = (ProcResult<T>, Vec<TaskHandle<T>>);
 
 pub struct SelectTasks<T: Send + 'static> {
     runtime: TaskRuntime,
-    tasks: Vec<JoinHandle<T>>,
+    tasks: Vec<TaskHandle<T>>,
+    aborts: Vec<AbortHandle>,
+    pending: Option<Receiver<Selected<T>>>,
+    settled: Option<ProcStatus>,
+    drop_policy: DropPolicy,
 }
 
 impl<T: Send> Default for SelectTasks<T> {
     #[inline]
     fn default() -> Self {
-        let runtime = Handle::try_current()
-            .map(TaskRuntime::Entered)
-            .unwrap_or_else(|_| TaskRuntime::new());
         Self {
-            runtime,
+            runtime: TaskRuntime::current_or_global(),
             tasks: Default::default(),
+            aborts: Default::default(),
+            pending: None,
+            settled: None,
+            drop_policy: DropPolicy::default(),
         }
     }
 }
@@ -28,11 +39,22 @@ impl<T: Send + 'static> SelectTasks<T> {
         Self::default()
     }
 
+    #[cfg(feature = "tokio")]
+    #[inline]
+    pub fn with_runtime(handle: tokio::runtime::Handle) -> Self {
+        Self::with_spawner(crate::runners::TokioSpawner::new(handle))
+    }
+
+    /// Spawns the tasks onto an existing executor
     #[inline]
-    pub fn with_runtime(handle: Handle) -> Self {
+    pub fn with_spawner(spawner: impl Spawner + 'static) -> Self {
         Self {
-            runtime: TaskRuntime::Entered(handle),
+            runtime: TaskRuntime::with_spawner(spawner),
             tasks: Default::default(),
+            aborts: Default::default(),
+            pending: None,
+            settled: None,
+            drop_policy: DropPolicy::default(),
         }
     }
 
@@ -42,42 +64,194 @@ impl<T: Send + 'static> SelectTasks<T> {
         F: IntoFuture<Output = T>,
         <F as IntoFuture>::IntoFuture: Send + 'static,
     {
-        self.tasks.push(Handle::current().spawn(fut.into_future()));
+        let task = self.runtime.spawn(fut.into_future());
+        self.aborts.push(task.abort_handle());
+        self.tasks.push(task);
         self
     }
+
+    /// Awaits the first task in the background, unless this was already started by a previous join
+    fn pending(&mut self) -> &Receiver<Selected<T>> {
+        let tasks = &mut self.tasks;
+        let runtime = &self.runtime;
+        self.pending.get_or_insert_with(|| {
+            let tasks = std::mem::take(tasks);
+            let (output_tx, output_rx) = flume::bounded(1);
+            runtime.spawn(async move {
+                let (finished, _, remaining) = futures::future::select_all(tasks).await;
+                let _ = output_tx.send_async((finished, remaining)).await;
+            });
+            output_rx
+        })
+    }
+
+    #[inline]
+    fn is_empty(&self) -> bool {
+        self.tasks.is_empty() && self.pending.is_none()
+    }
+
+    fn finish(&mut self, selected: Result<Selected<T>, ProcError>) -> ProcResult<Option<T>> {
+        self.pending = None;
+        self.settled = Some(ProcStatus::Joined);
+        let (output, remaining) = selected?;
+        self.aborts = remaining.iter().map(TaskHandle::abort_handle).collect();
+        self.tasks = remaining;
+        Ok(Some(output?))
+    }
 }
 
 impl<T: Send + 'static> Proc for SelectTasks<T> {
     type Output = Option<T>;
 
-    fn join(&mut self) -> anyhow::Result<Self::Output> {
-        if self.tasks.is_empty() {
+    fn join(&mut self) -> ProcResult<Self::Output> {
+        if self.is_empty() {
             return Ok(None);
         }
+        let pending = self.pending().clone();
+        let selected = self.runtime.block_in_place(|| pending.recv())?;
+        self.finish(selected.map_err(ProcError::from))
+    }
 
-        let tasks = std::mem::take(&mut self.tasks);
-        let (output_tx, output_rx) = flume::bounded(1);
-        self.runtime.handle().spawn(async move {
-            let (finished, _, remaining) = futures::future::select_all(tasks).await;
-            let _ = output_tx.send_async((finished, remaining));
-        });
-        let (output, remaining) = output_rx.recv()?;
-        self.tasks = remaining;
-        Ok(Some(output?))
+    fn join_deadline(&mut self, deadline: Instant) -> ProcResult<Self::Output> {
+        if self.is_empty() {
+            return Ok(None);
+        }
+        let pending = self.pending().clone();
+        // Only wait on the runtime when actually blocking, so polling never fails
+        let recv = || pending.recv_deadline(deadline);
+        let selected = if deadline > Instant::now() {
+            self.runtime.block_in_place(recv)?
+        } else {
+            recv()
+        };
+        let selected = match selected {
+            Err(flume::RecvTimeoutError::Timeout) => return Err(ProcError::TimedOut),
+            selected => selected.map_err(ProcError::from),
+        };
+        self.finish(selected)
+    }
+
+    fn status(&self) -> ProcStatus {
+        match (&self.pending, self.settled) {
+            (None, Some(settled)) if self.tasks.is_empty() => settled,
+            (Some(pending), _) if pending.is_empty() => ProcStatus::Running,
+            (Some(_), _) => ProcStatus::Finished,
+            (None, _) if self.tasks.iter().any(TaskHandle::is_finished) => ProcStatus::Finished,
+            (None, _) if self.tasks.is_empty() => ProcStatus::Finished,
+            (None, _) => ProcStatus::Running,
+        }
     }
 
     #[inline]
     fn forget(&mut self) {
-        for task in self.tasks.drain(..) {
+        for task in self.aborts.drain(..) {
             task.abort();
         }
+        self.tasks.clear();
+        self.pending = None;
+        self.settled = Some(ProcStatus::Forgotten);
+    }
+
+    /// Note: Detached tasks keep running, unless they were spawned onto a dedicated runtime
+    fn set_drop_policy(&mut self, policy: DropPolicy) {
+        self.drop_policy = policy;
     }
 }
 
-/// Note: Only awaits the first ready future
+/// Note: Only awaits the first ready future, the remaining ones are aborted unless detached
 impl<T: Send + 'static> Drop for SelectTasks<T> {
     fn drop(&mut self) {
-        let _ = self.join();
+        // Joining again would await the next of the remaining futures
+        if self.settled.is_none() {
+            self.drop_policy.apply(self);
+        }
+        if self.drop_policy != DropPolicy::Detach {
+            for task in self.aborts.drain(..) {
+                task.abort();
+            }
+        }
         self.runtime.shutdown();
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn select_2() {
+        let mut tasks = SelectTasks::new()
+            .or(async move { 1 })
+            .or(futures::future::pending());
+        assert_eq!(tasks.join().expect("could not join"), Some(1));
+    }
+
+    #[test]
+    fn aborts_remaining() {
+        let (tx, rx) = flume::bounded::<()>(0);
+        let mut tasks = SelectTasks::new().or(async move { 1 }).or(async move {
+            let _tx = tx;
+            futures::future::pending().await
+        });
+        assert_eq!(tasks.join().expect("could not join"), Some(1));
+        drop(tasks);
+        // Disconnects once the remaining task was dropped
+        assert!(rx.recv_timeout(Duration::from_secs(5)).is_err());
+        assert!(rx.is_disconnected());
+    }
+
+    #[test]
+    fn join_timeout() {
+        let (tx, rx) = flume::bounded::<()>(0);
+        let mut tasks = SelectTasks::new().or(async move {
+            let _ = rx.recv_async().await;
+            1
+        });
+        assert!(matches!(
+            tasks.join_timeout(Duration::from_millis(1)),
+            Err(ProcError::TimedOut)
+        ));
+        tx.send(()).unwrap();
+        assert_eq!(tasks.join().expect("could not join"), Some(1));
+    }
+
+    #[test]
+    fn select_0() {
+        let result = SelectTasks::<()>::new().join().expect("could not join");
+        assert_eq!(result, None)
+    }
+
+    #[test]
+    fn panicked() {
+        let result = SelectTasks::<()>::new()
+            .or(async move { panic!("boom") })
+            .join();
+        assert!(matches!(result, Err(ProcError::Panicked(panic)) if panic.message() == "boom"));
+    }
+
+    #[tokio::test]
+    async fn inside_current_thread_runtime() {
+        let mut tasks = SelectTasks::new()
+            .or(async move { 1 })
+            .or(futures::future::pending());
+        assert_eq!(tasks.join().expect("could not join"), Some(1));
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn would_deadlock() {
+        let mut tasks =
+            SelectTasks::with_runtime(tokio::runtime::Handle::current()).or(async move { 1 });
+        assert!(matches!(tasks.join(), Err(ProcError::WouldDeadlock)));
+    }
+
+    #[cfg(feature = "smol")]
+    #[test]
+    fn smol_spawner() {
+        let mut tasks = SelectTasks::with_spawner(crate::SmolSpawner::global())
+            .or(async move { 1 })
+            .or(futures::future::pending());
+        assert_eq!(tasks.join().expect("could not join"), Some(1));
+    }
+}
@@ -0,0 +1,151 @@
+use crate::drop_policy::DropPolicy;
+use crate::error::{ProcError, ProcResult};
+use crate::panic::catch_unwind;
+use crate::proc::{join_until, Proc, ProcStatus};
+use std::time::Instant;
+
+/// [`Proc`] combinator that lazily creates a second unit of execution from the output of the first
+pub struct ThenProc<L, F, R>
+where
+    L: Proc + Send,
+    F: FnOnce(L::Output) -> R + Send,
+    R: Proc + Send,
+{
+    pub(crate) left: L,
+    pub(crate) f: Option<F>,
+    pub(crate) right: Option<R>,
+    pub(crate) drop_policy: Option<DropPolicy>,
+}
+
+/// [`Proc`] combinator that joins the [`Proc`] returned by a unit of execution
+pub type FlattenProc<P> =
+    ThenProc<P, fn(<P as Proc>::Output) -> <P as Proc>::Output, <P as Proc>::Output>;
+
+impl<L, F, R> ThenProc<L, F, R>
+where
+    L: Proc + Send,
+    F: FnOnce(L::Output) -> R + Send,
+    R: Proc + Send,
+{
+    fn join_until(&mut self, deadline: Option<Instant>) -> ProcResult<R::Output> {
+        if self.right.is_none() {
+            if self.f.is_none() {
+                return Err(ProcError::AlreadyJoined);
+            }
+            match join_until(&mut self.left, deadline) {
+                Ok(output) => {
+                    if let Some(f) = self.f.take() {
+                        let mut right = catch_unwind(move || f(output))?;
+                        if let Some(policy) = self.drop_policy {
+                            right.set_drop_policy(policy);
+                        }
+                        self.right = Some(right);
+                    }
+                }
+                Err(ProcError::TimedOut) => return Err(ProcError::TimedOut),
+                Err(err) => {
+                    self.f = None;
+                    return Err(err);
+                }
+            }
+        }
+        match &mut self.right {
+            Some(right) => join_until(right, deadline),
+            None => Err(ProcError::AlreadyJoined),
+        }
+    }
+}
+
+impl<L, F, R> Proc for ThenProc<L, F, R>
+where
+    L: Proc + Send,
+    F: FnOnce(L::Output) -> R + Send,
+    R: Proc + Send,
+{
+    type Output = R::Output;
+
+    fn join(&mut self) -> ProcResult<Self::Output> {
+        self.join_until(None)
+    }
+
+    fn join_deadline(&mut self, deadline: Instant) -> ProcResult<Self::Output> {
+        self.join_until(Some(deadline))
+    }
+
+    fn status(&self) -> ProcStatus {
+        match (&self.right, &self.f) {
+            (Some(right), _) => right.status(),
+            (None, Some(_)) if self.left.status() == ProcStatus::Finished => ProcStatus::Pending,
+            (None, _) => self.left.status(),
+        }
+    }
+
+    fn forget(&mut self) {
+        self.left.forget();
+        self.f = None;
+        if let Some(right) = &mut self.right {
+            right.forget();
+        }
+    }
+
+    fn set_drop_policy(&mut self, policy: DropPolicy) {
+        self.drop_policy = Some(policy);
+        self.left.set_drop_policy(policy);
+        if let Some(right) = &mut self.right {
+            right.set_drop_policy(policy);
+        }
+    }
+}
+
+impl<L, F, R> Drop for ThenProc<L, F, R>
+where
+    L: Proc + Send,
+    F: FnOnce(L::Output) -> R + Send,
+    R: Proc + Send,
+{
+    fn drop(&mut self) {
+        self.drop_policy.unwrap_or_default().apply(self);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::proc::Proc;
+    use crate::proc_ext::ProcExt;
+    use crate::{blocking, thread};
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::Arc;
+
+    #[test]
+    fn feeds_output() {
+        let parsed = thread(|| Ok(b"42".to_vec()))
+            .then(|bytes| blocking(move || Ok(String::from_utf8(bytes)?.parse::<u64>()?)))
+            .join()
+            .expect("could not join");
+        assert_eq!(parsed, 42);
+    }
+
+    #[test]
+    fn lazy_on_failure() {
+        let counter = Arc::new(AtomicU64::new(0));
+        let created = counter.clone();
+        let result = blocking(|| -> anyhow::Result<u64> { anyhow::bail!("failed") })
+            .then(move |val| {
+                created.fetch_add(1, Ordering::SeqCst);
+                blocking(move || Ok(val))
+            })
+            .join();
+        assert!(result.is_err());
+        assert_eq!(counter.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn join_on_drop() {
+        let counter = Arc::new(AtomicU64::new(0));
+        let added = counter.clone();
+        let joined = blocking(|| Ok(2))
+            .then(move |val| blocking(move || Ok(added.fetch_add(val, Ordering::SeqCst))));
+        drop(joined);
+        assert_eq!(counter.load(Ordering::Relaxed), 2);
+    }
+}
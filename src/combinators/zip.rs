@@ -0,0 +1,288 @@
+use crate::drop_policy::DropPolicy;
+use crate::error::{ProcError, ProcResult};
+use crate::proc::{join_until, Proc, ProcStatus};
+use flume::Sender;
+use std::sync::Arc;
+use std::task::{Wake, Waker};
+use std::time::Instant;
+
+/// Wakes a fail-fast [`ZipProc`] once either of its units of execution completed
+struct Signal(Sender<()>);
+
+impl Wake for Signal {
+    fn wake(self: Arc<Self>) {
+        let _ = self.0.try_send(());
+    }
+}
+
+/// [`Proc`] combinator that joins two units of execution and returns both outputs
+pub struct ZipProc<L, R>
+where
+    L: Proc + Send,
+    R: Proc + Send,
+{
+    pub(crate) left: L,
+    pub(crate) right: R,
+    pub(crate) left_output: Option<ProcResult<L::Output>>,
+    pub(crate) right_output: Option<ProcResult<R::Output>>,
+    pub(crate) fail_fast: bool,
+    pub(crate) joined: bool,
+    pub(crate) drop_policy: DropPolicy,
+}
+
+impl<L, R> ZipProc<L, R>
+where
+    L: Proc + Send,
+    R: Proc + Send,
+{
+    /// Returns the first error as soon as it occurs and forgets the other unit of execution,
+    /// instead of waiting for both to complete
+    pub fn fail_fast(mut self) -> Self {
+        self.fail_fast = true;
+        self
+    }
+
+    fn join_until(&mut self, deadline: Option<Instant>) -> ProcResult<(L::Output, R::Output)> {
+        if self.joined {
+            return Err(ProcError::AlreadyJoined);
+        }
+        if self.fail_fast {
+            self.poll_until(deadline)?;
+        } else {
+            if self.left_output.is_none() {
+                self.left_output = Some(join_until(&mut self.left, deadline));
+            }
+            if self.right_output.is_none() {
+                self.right_output = Some(join_until(&mut self.right, deadline));
+            }
+        }
+        self.take_outputs()
+    }
+
+    /// Waits until both units of execution completed or one failed. Units of execution which
+    /// cannot notify their completion, e.g. foreground [`Proc`]s, are joined in turn instead
+    fn poll_until(&mut self, deadline: Option<Instant>) -> ProcResult<()> {
+        let (signal, woken) = flume::bounded(1);
+        let waker = Waker::from(Arc::new(Signal(signal)));
+        loop {
+            // Registers before polling, such that a completion in between is not missed
+            let left = self.left_output.is_some() || self.left.register_waker(&waker);
+            let right = self.right_output.is_some() || self.right.register_waker(&waker);
+            let now = Instant::now();
+            if self.left_output.is_none() {
+                self.left_output = Some(self.left.join_deadline(now));
+            }
+            if self.right_output.is_none() {
+                self.right_output = Some(self.right.join_deadline(now));
+            }
+            self.clear_timeouts();
+            if matches!(self.left_output, Some(Err(_))) || matches!(self.right_output, Some(Err(_)))
+            {
+                self.left.forget();
+                self.right.forget();
+                return Ok(());
+            }
+            if self.left_output.is_some() && self.right_output.is_some() {
+                return Ok(());
+            }
+            if deadline.is_some_and(|deadline| now >= deadline) {
+                return Err(ProcError::TimedOut);
+            }
+            if !left {
+                self.left_output = Some(join_until(&mut self.left, deadline));
+            } else if !right {
+                self.right_output = Some(join_until(&mut self.right, deadline));
+            } else {
+                match deadline {
+                    Some(deadline) => {
+                        let _ = woken.recv_deadline(deadline);
+                    }
+                    None => {
+                        let _ = woken.recv();
+                    }
+                }
+            }
+        }
+    }
+
+    /// Returns both outputs, or the first error
+    fn take_outputs(&mut self) -> ProcResult<(L::Output, R::Output)> {
+        self.clear_timeouts();
+        match (self.left_output.take(), self.right_output.take()) {
+            (Some(Err(err)), _) | (_, Some(Err(err))) => {
+                self.joined = true;
+                Err(err)
+            }
+            (Some(Ok(left)), Some(Ok(right))) => {
+                self.joined = true;
+                Ok((left, right))
+            }
+            (left, right) => {
+                self.left_output = left;
+                self.right_output = right;
+                Err(ProcError::TimedOut)
+            }
+        }
+    }
+
+    /// Allows timed out units of execution to be joined again
+    fn clear_timeouts(&mut self) {
+        if matches!(self.left_output, Some(Err(ProcError::TimedOut))) {
+            self.left_output = None;
+        }
+        if matches!(self.right_output, Some(Err(ProcError::TimedOut))) {
+            self.right_output = None;
+        }
+    }
+}
+
+impl<L, R> Proc for ZipProc<L, R>
+where
+    L: Proc + Send,
+    R: Proc + Send,
+{
+    type Output = (L::Output, R::Output);
+
+    fn join(&mut self) -> ProcResult<Self::Output> {
+        self.join_until(None)
+    }
+
+    fn join_deadline(&mut self, deadline: Instant) -> ProcResult<Self::Output> {
+        self.join_until(Some(deadline))
+    }
+
+    fn status(&self) -> ProcStatus {
+        if self.joined {
+            return ProcStatus::Joined;
+        }
+        let left = match self.left_output {
+            Some(_) => ProcStatus::Finished,
+            None => self.left.status(),
+        };
+        let right = match self.right_output {
+            Some(_) => ProcStatus::Finished,
+            None => self.right.status(),
+        };
+        match (left, right) {
+            (ProcStatus::Forgotten, _) | (_, ProcStatus::Forgotten) => ProcStatus::Forgotten,
+            (ProcStatus::Running, _) | (_, ProcStatus::Running) => ProcStatus::Running,
+            (ProcStatus::Finished, ProcStatus::Finished) => ProcStatus::Finished,
+            _ => ProcStatus::Pending,
+        }
+    }
+
+    fn forget(&mut self) {
+        self.left.forget();
+        self.right.forget();
+    }
+
+    fn set_drop_policy(&mut self, policy: DropPolicy) {
+        self.drop_policy = policy;
+        self.left.set_drop_policy(policy);
+        self.right.set_drop_policy(policy);
+    }
+
+    /// Wakes once either unit of execution completed, which allows nested fail-fast
+    /// [`ZipProc`]s to observe all of them
+    fn register_waker(&mut self, waker: &Waker) -> bool {
+        let left = self.left_output.is_some() || self.left.register_waker(waker);
+        let right = self.right_output.is_some() || self.right.register_waker(waker);
+        left && right
+    }
+}
+
+impl<L, R> Drop for ZipProc<L, R>
+where
+    L: Proc + Send,
+    R: Proc + Send,
+{
+    fn drop(&mut self) {
+        self.drop_policy.apply(self);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::error::ProcError;
+    use crate::proc::Proc;
+    use crate::proc_ext::ProcExt;
+    use crate::{blocking, cancellable_thread, thread};
+    use std::time::Duration;
+
+    #[test]
+    fn zip() {
+        let output = thread(|| Ok(1))
+            .zip(blocking(|| Ok("two")))
+            .join()
+            .expect("could not join");
+        assert_eq!(output, (1, "two"));
+    }
+
+    #[test]
+    fn wait_for_all() {
+        let (tx, rx) = flume::bounded(1);
+        let result = blocking(|| -> anyhow::Result<()> { anyhow::bail!("failed") })
+            .zip(thread(move || Ok(tx.send(())?)))
+            .join();
+        assert!(result.unwrap_err().user_error().is_some());
+        assert!(rx.try_recv().is_ok());
+    }
+
+    #[test]
+    fn fail_fast() {
+        let result = cancellable_thread(|token| {
+            token.wait();
+            token.check()?;
+            Ok(())
+        })
+        .zip(thread(|| -> anyhow::Result<()> { anyhow::bail!("failed") }))
+        .fail_fast()
+        .join();
+        assert!(result.unwrap_err().user_error().is_some());
+    }
+
+    #[test]
+    fn fail_fast_nested() {
+        let wait = || {
+            cancellable_thread(|token| {
+                token.wait();
+                token.check()?;
+                Ok(())
+            })
+        };
+        let result = wait()
+            .zip(wait())
+            .fail_fast()
+            .zip(thread(|| -> anyhow::Result<()> { anyhow::bail!("failed") }))
+            .fail_fast()
+            .join_timeout(Duration::from_secs(5));
+        assert!(result.unwrap_err().user_error().is_some());
+    }
+
+    #[test]
+    fn join_timeout() {
+        let (tx, rx) = flume::bounded::<()>(0);
+        let mut zipped = thread(|| Ok(1))
+            .zip(thread(move || Ok(rx.recv()?)))
+            .fail_fast();
+        assert!(matches!(
+            zipped.join_timeout(Duration::from_millis(20)),
+            Err(ProcError::TimedOut)
+        ));
+        tx.send(()).unwrap();
+        assert_eq!(zipped.join().expect("could not join"), (1, ()));
+    }
+
+    #[test]
+    fn join_macro() {
+        let (a, b, c) = crate::join!(thread(|| Ok(1)), blocking(|| Ok('b')), thread(|| Ok("c")))
+            .expect("could not join");
+        assert_eq!((a, b, c), (1, 'b', "c"));
+
+        let result = crate::try_join!(
+            thread(|| Ok(1)),
+            blocking(|| -> anyhow::Result<()> { anyhow::bail!("failed") })
+        );
+        assert!(result.is_err());
+    }
+}
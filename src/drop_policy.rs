@@ -0,0 +1,157 @@
+use crate::proc::{Proc, ProcStatus};
+use std::time::Duration;
+
+/// Determines what happens to a [`Proc`] which is dropped before it was joined.
+/// Set through [`ProcExt::on_drop`](crate::ProcExt::on_drop), combinators pass it on to the
+/// [`Proc`]s they contain. A forgotten [`Proc`] is detached, regardless of the policy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DropPolicy {
+    /// Joins the unit of execution, running foreground [`Proc`]s to completion
+    #[default]
+    Join,
+    /// Lets background units of execution run to completion unobserved.
+    /// Foreground [`Proc`]s are not executed.
+    Detach,
+    /// Forgets the unit of execution, cancelling or aborting it where supported, without waiting
+    Cancel,
+    /// Joins the unit of execution, but cancels it if it does not complete within the timeout
+    JoinTimeout(Duration),
+    /// Panics when a [`Proc`] is dropped before it was joined or forgotten.
+    /// Only enabled with debug assertions, joins otherwise
+    PanicIfUnjoined,
+}
+
+impl DropPolicy {
+    /// Applies the policy to a [`Proc`] which is being dropped.
+    /// While the thread is unwinding, the [`Proc`] is forgotten instead, as blocking
+    /// could hang the unwind and panicking would abort the process.
+    pub(crate) fn apply<P: Proc + ?Sized>(self, proc: &mut P) {
+        if std::thread::panicking() {
+            proc.forget();
+            return;
+        }
+        if proc.status() == ProcStatus::Forgotten {
+            return;
+        }
+        match self {
+            Self::Join => {
+                let _ = proc.join();
+            }
+            Self::Detach => {}
+            Self::Cancel => proc.forget(),
+            Self::JoinTimeout(timeout) => {
+                if matches!(proc.join_timeout(timeout), Err(err) if err.is_timeout()) {
+                    proc.forget();
+                }
+            }
+            Self::PanicIfUnjoined => {
+                if cfg!(debug_assertions)
+                    && !matches!(proc.status(), ProcStatus::Joined | ProcStatus::Forgotten)
+                {
+                    panic!("Proc was dropped without being joined");
+                }
+                let _ = proc.join();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::proc_ext::ProcExt;
+    use crate::{blocking, cancellable_thread, nop, thread};
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::Arc;
+
+    fn wait_for_cancel(counter: Arc<AtomicU64>) -> impl Proc {
+        cancellable_thread(move |token| {
+            token.wait();
+            counter.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn detach() {
+        let (tx, rx) = flume::bounded::<()>(0);
+        let counter = Arc::new(AtomicU64::new(0));
+        let ran = counter.clone();
+        drop(
+            thread(move || Ok(rx.recv()?))
+                .and_then(blocking(move || Ok(ran.fetch_add(1, Ordering::SeqCst))))
+                .on_drop(DropPolicy::Detach),
+        );
+        tx.send(()).unwrap();
+        assert_eq!(counter.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn cancel() {
+        let counter = Arc::new(AtomicU64::new(0));
+        drop(wait_for_cancel(counter.clone()).on_drop(DropPolicy::Cancel));
+        while counter.load(Ordering::Relaxed) == 0 {
+            std::thread::yield_now();
+        }
+    }
+
+    #[test]
+    fn join_timeout() {
+        let counter = Arc::new(AtomicU64::new(0));
+        let proc = wait_for_cancel(counter.clone())
+            .zip(blocking(|| Ok(())))
+            .on_drop(DropPolicy::JoinTimeout(Duration::from_millis(10)));
+        drop(proc);
+        while counter.load(Ordering::Relaxed) == 0 {
+            std::thread::yield_now();
+        }
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "without being joined")]
+    fn panic_if_unjoined() {
+        drop(blocking(|| Ok(())).on_drop(DropPolicy::PanicIfUnjoined));
+    }
+
+    #[test]
+    fn panic_if_joined() {
+        let mut proc = blocking(|| Ok(())).on_drop(DropPolicy::PanicIfUnjoined);
+        proc.join().expect("could not join");
+    }
+
+    #[test]
+    fn panic_if_joined_nop() {
+        let mut proc = thread(|| Ok(()))
+            .and_then(nop())
+            .on_drop(DropPolicy::PanicIfUnjoined);
+        proc.join().expect("could not join");
+        assert_eq!(proc.status(), ProcStatus::Joined);
+        assert!(matches!(proc.join(), Err(crate::ProcError::AlreadyJoined)));
+    }
+
+    #[test]
+    fn forgotten() {
+        let (tx, rx) = flume::bounded::<()>(0);
+        for policy in [DropPolicy::Join, DropPolicy::PanicIfUnjoined] {
+            let rx = rx.clone();
+            let mut proc = thread(move || Ok(rx.recv()?)).on_drop(policy);
+            proc.forget();
+            drop(proc);
+        }
+        drop(tx);
+    }
+
+    #[test]
+    fn forget_while_unwinding() {
+        let counter = Arc::new(AtomicU64::new(0));
+        let result = std::panic::catch_unwind(|| {
+            let _proc = wait_for_cancel(counter.clone()).on_drop(DropPolicy::PanicIfUnjoined);
+            panic!("boom");
+        });
+        assert!(result.is_err());
+        while counter.load(Ordering::Relaxed) == 0 {
+            std::thread::yield_now();
+        }
+    }
+}
@@ -0,0 +1,156 @@
+use crate::panic::PanicError;
+use std::any::Any;
+use std::fmt;
+
+/// Result of joining a [`Proc`](crate::Proc)
+pub type ProcResult<T> = Result<T, ProcError>;
+
+/// Reasons why joining a [`Proc`](crate::Proc) did not produce an output
+#[derive(Debug)]
+pub enum ProcError {
+    /// The output was already taken by a previous join, or the proc was forgotten
+    AlreadyJoined,
+    /// The unit of execution panicked
+    Panicked(PanicError),
+    /// The unit of execution was cancelled or aborted before completion
+    Cancelled,
+    /// The deadline passed before the unit of execution completed
+    TimedOut,
+    /// The runtime driving the unit of execution was shut down
+    RuntimeShutdown,
+    /// Joining would block the current-thread runtime which drives the unit of execution
+    WouldDeadlock,
+    /// The unit of execution returned an error
+    User(anyhow::Error),
+    /// Both the primary unit of execution and its fallback failed
+    Fallback {
+        primary: Box<ProcError>,
+        fallback: Box<ProcError>,
+    },
+}
+
+impl ProcError {
+    /// Builds a [`ProcError::Panicked`] from the payload of a caught panic
+    pub(crate) fn from_panic(payload: Box<dyn Any + Send>) -> Self {
+        Self::Panicked(PanicError::new(payload, None))
+    }
+
+    /// Returns the user error, if the unit of execution itself failed
+    pub fn user_error(&self) -> Option<&anyhow::Error> {
+        match self {
+            Self::User(err) => Some(err),
+            _ => None,
+        }
+    }
+
+    #[inline]
+    pub fn is_panic(&self) -> bool {
+        matches!(self, Self::Panicked(_))
+    }
+
+    /// Continues unwinding with the original payload if the unit of execution panicked,
+    /// otherwise returns the error unchanged
+    pub fn resume_if_panicked(self) -> Self {
+        match self {
+            Self::Panicked(panic) => panic.resume_unwind(),
+            err => err,
+        }
+    }
+
+    #[inline]
+    pub fn is_timeout(&self) -> bool {
+        matches!(self, Self::TimedOut)
+    }
+
+    #[inline]
+    pub fn is_cancelled(&self) -> bool {
+        matches!(self, Self::Cancelled)
+    }
+}
+
+impl fmt::Display for ProcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::AlreadyJoined => write!(f, "Nothing to join"),
+            Self::Panicked(panic) => write!(f, "Panicked: {panic}"),
+            Self::Cancelled => write!(f, "Cancelled"),
+            Self::TimedOut => write!(f, "Timed out"),
+            Self::RuntimeShutdown => write!(f, "Runtime was shut down"),
+            Self::WouldDeadlock => write!(f, "Joining would deadlock the runtime"),
+            Self::User(err) => fmt::Display::fmt(err, f),
+            Self::Fallback { primary, fallback } => {
+                write!(f, "{fallback} (primary failed: {primary})")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ProcError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Panicked(panic) => Some(panic),
+            Self::User(err) => Some(err.as_ref()),
+            Self::Fallback { primary, .. } => Some(primary.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<PanicError> for ProcError {
+    fn from(panic: PanicError) -> Self {
+        Self::Panicked(panic)
+    }
+}
+
+impl From<anyhow::Error> for ProcError {
+    /// Wraps a user error, unless it is a [`ProcError`] that was propagated through `?`
+    fn from(err: anyhow::Error) -> Self {
+        err.downcast::<ProcError>().unwrap_or_else(Self::User)
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl From<tokio::task::JoinError> for ProcError {
+    fn from(err: tokio::task::JoinError) -> Self {
+        if err.is_panic() {
+            Self::from_panic(err.into_panic())
+        } else {
+            Self::Cancelled
+        }
+    }
+}
+
+impl From<flume::RecvError> for ProcError {
+    fn from(_: flume::RecvError) -> Self {
+        Self::RuntimeShutdown
+    }
+}
+
+impl From<flume::RecvTimeoutError> for ProcError {
+    fn from(err: flume::RecvTimeoutError) -> Self {
+        match err {
+            flume::RecvTimeoutError::Timeout => Self::TimedOut,
+            flume::RecvTimeoutError::Disconnected => Self::RuntimeShutdown,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn flattens_propagated_errors() {
+        let err = anyhow::Error::from(ProcError::Cancelled);
+        assert!(ProcError::from(err).is_cancelled());
+    }
+
+    #[test]
+    fn wraps_user_errors() {
+        let err = ProcError::from(anyhow::anyhow!("boom"));
+        assert_eq!(
+            err.user_error().map(ToString::to_string),
+            Some("boom".into())
+        );
+    }
+}
@@ -0,0 +1,177 @@
+use crate::error::{ProcError, ProcResult};
+use crate::proc::Proc;
+use flume::r#async::RecvFut;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// Future which completes once a [`Proc`] is joined, created by
+/// [`ProcExt::join_async`](crate::ProcExt::join_async).
+/// [`Proc`]s which support completion notifications are awaited in place,
+/// all others are joined on a blocking thread, so the executor is never blocked
+pub struct ProcFuture<P: Proc + 'static> {
+    state: State<P>,
+}
+
+enum State<P: Proc + 'static> {
+    Idle(P),
+    Notified(P),
+    Offloaded(RecvFut<'static, ProcResult<P::Output>>),
+    Done,
+}
+
+impl<P: Proc + 'static> ProcFuture<P> {
+    pub(crate) fn new(proc: P) -> Self {
+        Self {
+            state: State::Idle(proc),
+        }
+    }
+}
+
+impl<P: Proc + 'static> Future for ProcFuture<P> {
+    type Output = ProcResult<P::Output>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        loop {
+            match std::mem::replace(&mut self.state, State::Done) {
+                State::Idle(mut proc) | State::Notified(mut proc) => {
+                    // Only polled once it can notify its completion, as joining
+                    // a foreign `Proc` may block, even through `try_join`
+                    if !proc.register_waker(cx.waker()) {
+                        let (tx, rx) = flume::bounded(1);
+                        offload(move || {
+                            let _ = tx.send(proc.join());
+                        });
+                        self.state = State::Offloaded(rx.into_recv_async());
+                        continue;
+                    }
+                    // Registered first, such that a completion in between is not missed
+                    if let Poll::Ready(output) = proc.try_join() {
+                        return Poll::Ready(output);
+                    }
+                    self.state = State::Notified(proc);
+                    return Poll::Pending;
+                }
+                State::Offloaded(mut output) => {
+                    return match Pin::new(&mut output).poll(cx) {
+                        Poll::Ready(output) => {
+                            Poll::Ready(output.unwrap_or_else(|err| Err(ProcError::from(err))))
+                        }
+                        Poll::Pending => {
+                            self.state = State::Offloaded(output);
+                            Poll::Pending
+                        }
+                    };
+                }
+                State::Done => return Poll::Ready(Err(ProcError::AlreadyJoined)),
+            }
+        }
+    }
+}
+
+// The proc is never pinned, it is only moved out of the future when offloaded
+impl<P: Proc + 'static> Unpin for ProcFuture<P> {}
+
+impl<P: Proc + 'static> Drop for ProcFuture<P> {
+    /// Drops a pending [`Proc`] on a blocking thread, as its drop policy may block
+    fn drop(&mut self) {
+        if let State::Idle(proc) | State::Notified(proc) =
+            std::mem::replace(&mut self.state, State::Done)
+        {
+            offload(move || drop(proc));
+        }
+    }
+}
+
+/// Runs `f` on the blocking pool of the ambient tokio runtime, or a new thread otherwise
+fn offload(f: impl FnOnce() + Send + 'static) {
+    #[cfg(feature = "tokio")]
+    if let Ok(handle) = tokio::runtime::Handle::try_current() {
+        handle.spawn_blocking(f);
+        return;
+    }
+    std::thread::spawn(f);
+}
+
+#[cfg(test)]
+mod test {
+    use crate::drop_policy::DropPolicy;
+    use crate::error::{ProcError, ProcResult};
+    use crate::proc::Proc;
+    use crate::proc_ext::ProcExt;
+    use crate::{blocking, cancellable_thread, thread};
+    use std::time::{Duration, Instant};
+
+    #[tokio::test]
+    async fn await_blocking() {
+        let output = blocking(|| Ok(1))
+            .and_then(blocking(|| Ok(2)))
+            .join_async()
+            .await;
+        assert_eq!(output.expect("could not join"), 2);
+    }
+
+    #[tokio::test]
+    async fn await_thread() {
+        let (tx, rx) = flume::bounded::<()>(0);
+        let proc = thread(move || Ok(rx.recv()?)).map(|_| 1).join_async();
+        let (output, _) = tokio::join!(proc, tx.send_async(()));
+        assert_eq!(output.expect("could not join"), 1);
+    }
+
+    #[tokio::test]
+    async fn await_failure() {
+        let err = blocking(|| -> anyhow::Result<()> { anyhow::bail!("boom") })
+            .join_async()
+            .await
+            .unwrap_err();
+        assert!(err.user_error().is_some());
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn does_not_block_executor() {
+        let proc = cancellable_thread(|token| {
+            token.wait();
+            Ok(())
+        })
+        .on_drop(DropPolicy::Cancel);
+        let timeout = tokio::time::timeout(Duration::from_millis(10), proc.join_async());
+        assert!(timeout.await.is_err());
+    }
+
+    #[test]
+    fn without_runtime() {
+        let output = futures::executor::block_on(blocking(|| Ok(1)).join_async());
+        assert_eq!(output.expect("could not join"), 1);
+        let err = futures::executor::block_on(
+            thread(|| -> anyhow::Result<()> { Err(ProcError::Cancelled.into()) }).join_async(),
+        );
+        assert!(err.unwrap_err().is_cancelled());
+    }
+
+    /// Implements only the required methods, with a blocking join
+    struct Slow;
+
+    impl Proc for Slow {
+        type Output = ();
+
+        fn join(&mut self) -> ProcResult<()> {
+            std::thread::sleep(Duration::from_millis(200));
+            Ok(())
+        }
+
+        fn forget(&mut self) {}
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn offloads_foreign_proc() {
+        let started = Instant::now();
+        let timer = async {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            started.elapsed()
+        };
+        let (output, fired) = tokio::join!(Slow.join_async(), timer);
+        output.expect("could not join");
+        assert!(fired < Duration::from_millis(150));
+    }
+}
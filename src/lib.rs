@@ -1,11 +1,25 @@
+#[cfg(all(feature = "async", not(any(feature = "tokio", feature = "smol"))))]
+compile_error!("The `async` feature requires an executor backend, enable `tokio` or `smol`");
+
+mod cancel;
 mod combinators;
+mod drop_policy;
+mod error;
+mod future;
+mod macros;
+mod panic;
 mod proc;
 mod proc_ext;
 mod runners;
 
+pub use crate::cancel::CancellationToken;
 pub use crate::combinators::*;
+pub use crate::drop_policy::DropPolicy;
+pub use crate::error::{ProcError, ProcResult};
+pub use crate::future::ProcFuture;
+pub use crate::panic::PanicError;
 pub use crate::runners::*;
-pub use proc::Proc;
+pub use proc::{Proc, ProcStatus};
 pub use proc_ext::ProcExt;
 
 /// Execute a future to completion using a tokio current-thread scheduler.
@@ -22,15 +36,15 @@ pub fn tokio<T: Send>(
 }
 
 /// Execute a future to completion using a [`smol::LocalExecutor`].
-// #[cfg(feature = "smol")]
-// pub fn smol<T: Send>(
-//     fut: impl std::future::Future<Output = anyhow::Result<T>> + Send + 'static,
-// ) -> BlockingProc<impl FnOnce() -> anyhow::Result<T>, T> {
-//     blocking(move || {
-//         let executor = smol::LocalExecutor::default();
-//         smol::block_on(executor.run(fut))
-//     })
-// }
+#[cfg(feature = "smol")]
+pub fn smol<T: Send>(
+    fut: impl std::future::Future<Output = anyhow::Result<T>> + Send + 'static,
+) -> BlockingProc<impl FnOnce() -> anyhow::Result<T>, T> {
+    blocking(move || {
+        let executor = smol::LocalExecutor::default();
+        smol::block_on(executor.run(fut))
+    })
+}
 
 /// Executes a function to completion using a blocking call
 pub fn blocking<F, T>(f: F) -> BlockingProc<F, T>
@@ -38,7 +52,27 @@ where
     F: FnOnce() -> anyhow::Result<T> + Send,
     T: Send,
 {
-    BlockingProc(Some(f))
+    BlockingProc {
+        f: Some(f),
+        token: CancellationToken::new(),
+        drop_policy: DropPolicy::default(),
+    }
+}
+
+/// Executes a function to completion using a blocking call.
+/// The function receives a [`CancellationToken`] which is cancelled when the [`Proc`] is forgotten
+pub fn cancellable_blocking<F, T>(f: F) -> BlockingProc<impl FnOnce() -> anyhow::Result<T>, T>
+where
+    F: FnOnce(CancellationToken) -> anyhow::Result<T> + Send,
+    T: Send,
+{
+    let token = CancellationToken::new();
+    let ctx = token.clone();
+    BlockingProc {
+        f: Some(move || f(ctx)),
+        token,
+        drop_policy: DropPolicy::default(),
+    }
 }
 
 /// Executes a function to completion on a native OS thread
@@ -47,11 +81,56 @@ where
     F: FnOnce() -> anyhow::Result<T> + Send + 'static,
     T: Send + 'static,
 {
-    NativeThread(Some(std::thread::spawn(f)))
+    cancellable_thread(move |_| f())
+}
+
+/// Executes a function to completion on a native OS thread.
+/// The function receives a [`CancellationToken`] which is cancelled when the [`Proc`] is forgotten
+pub fn cancellable_thread<F, T>(f: F) -> NativeThread<T>
+where
+    F: FnOnce(CancellationToken) -> anyhow::Result<T> + Send + 'static,
+    T: Send + 'static,
+{
+    NativeThread::spawn(f)
+}
+
+/// Runs a child OS process to completion, capturing its stdout & stderr.
+/// Forgetting the [`Proc`] terminates the process, see [`ChildProcess::grace_period`]
+pub fn command(command: std::process::Command) -> ChildProcess {
+    ChildProcess::spawn(command)
+}
+
+/// Creates a [`Scope`] for spawning [`Proc`]s which may borrow non-`'static` data.
+/// All threads spawned within the scope are joined before it returns, see [`std::thread::scope`]
+pub fn scope<'env, F, T>(f: F) -> T
+where
+    F: for<'scope> FnOnce(&Scope<'scope, 'env>) -> T,
+{
+    std::thread::scope(|inner| f(&Scope { inner }))
+}
+
+/// Re-instantiates a [`Proc`] from the factory until it succeeds, as allowed by the [`RetryPolicy`]
+pub fn retry<F, P>(factory: F, policy: RetryPolicy) -> RetryProc<F, P>
+where
+    F: FnMut() -> P + Send,
+    P: Proc + Send,
+{
+    RetryProc {
+        factory,
+        policy,
+        current: None,
+        attempts: 0,
+        started: None,
+        retry_at: None,
+        settled: None,
+        drop_policy: None,
+    }
 }
 
 /// Immediately returns without executing.
 /// Useful for building recursive [`Proc`] chains
 pub fn nop() -> NopProc {
-    NopProc {}
+    NopProc {
+        status: ProcStatus::Finished,
+    }
 }
@@ -0,0 +1,69 @@
+/// Joins two to six [`Proc`](crate::Proc)s of any output type, waiting for all of them to
+/// complete. Evaluates to a [`ProcResult`](crate::ProcResult) of a tuple of their outputs.
+/// Nest the invocations, or zip the [`Proc`](crate::Proc)s, to join more of them.
+///
+/// ```
+/// use circuits::{blocking, thread};
+///
+/// let (a, b, c) = circuits::join!(thread(|| Ok(1)), blocking(|| Ok("b")), thread(|| Ok(3.0)))
+///     .expect("Could not join");
+/// ```
+///
+/// ```compile_fail
+/// let a = circuits::join!(circuits::thread(|| Ok(1)));
+/// ```
+#[macro_export]
+macro_rules! join {
+    ($($proc:expr),+ $(,)?) => {
+        $crate::__zip_join!(wait_all; $($proc),+)
+    };
+}
+
+/// Similar to [`join!`], but returns the first error as soon as it occurs
+/// and forgets the remaining [`Proc`](crate::Proc)s. Takes two to six of them as well.
+#[macro_export]
+macro_rules! try_join {
+    ($($proc:expr),+ $(,)?) => {
+        $crate::__zip_join!(fail_fast; $($proc),+)
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __zip_join {
+    ($mode:ident; $a:expr, $b:expr) => {
+        $crate::Proc::join(&mut $crate::__zip!($mode; $a, $b))
+    };
+    ($mode:ident; $a:expr, $b:expr, $c:expr) => {
+        $crate::Proc::join(&mut $crate::__zip!($mode; $a, $b, $c)).map(|(a, (b, c))| (a, b, c))
+    };
+    ($mode:ident; $a:expr, $b:expr, $c:expr, $d:expr) => {
+        $crate::Proc::join(&mut $crate::__zip!($mode; $a, $b, $c, $d))
+            .map(|(a, (b, (c, d)))| (a, b, c, d))
+    };
+    ($mode:ident; $a:expr, $b:expr, $c:expr, $d:expr, $e:expr) => {
+        $crate::Proc::join(&mut $crate::__zip!($mode; $a, $b, $c, $d, $e))
+            .map(|(a, (b, (c, (d, e))))| (a, b, c, d, e))
+    };
+    ($mode:ident; $a:expr, $b:expr, $c:expr, $d:expr, $e:expr, $f:expr) => {
+        $crate::Proc::join(&mut $crate::__zip!($mode; $a, $b, $c, $d, $e, $f))
+            .map(|(a, (b, (c, (d, (e, f)))))| (a, b, c, d, e, f))
+    };
+    ($mode:ident; $($proc:expr),+) => {
+        compile_error!("join! and try_join! take between 2 and 6 procs")
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __zip {
+    (wait_all; $a:expr, $b:expr) => {
+        $crate::ProcExt::zip($a, $b)
+    };
+    (fail_fast; $a:expr, $b:expr) => {
+        $crate::ProcExt::zip($a, $b).fail_fast()
+    };
+    ($mode:ident; $a:expr, $($rest:expr),+) => {
+        $crate::__zip!($mode; $a, $crate::__zip!($mode; $($rest),+))
+    };
+}
@@ -0,0 +1,198 @@
+use std::any::Any;
+use std::cell::RefCell;
+use std::fmt;
+#[cfg(feature = "async")]
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::sync::{Mutex, Once};
+#[cfg(feature = "async")]
+use std::task::Poll;
+
+thread_local! {
+    static LAST_LOCATION: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+static HOOK: Once = Once::new();
+
+/// Panic captured while executing a [`Proc`](crate::Proc)
+pub struct PanicError {
+    message: String,
+    location: Option<String>,
+    payload: Mutex<Option<Box<dyn Any + Send>>>,
+}
+
+impl PanicError {
+    pub(crate) fn new(payload: Box<dyn Any + Send>, location: Option<String>) -> Self {
+        Self {
+            message: panic_message(&*payload),
+            location,
+            payload: Mutex::new(Some(payload)),
+        }
+    }
+
+    /// Message the unit of execution panicked with
+    #[inline]
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Source location of the panic, formatted as `file:line:column`.
+    /// Only available for panics caught by this crate while its panic hook was installed.
+    /// Panics which are re-raised through [`std::panic::resume_unwind`], e.g. by an executor
+    /// which caught them in a nested task, carry no location
+    #[inline]
+    pub fn location(&self) -> Option<&str> {
+        self.location.as_deref()
+    }
+
+    /// Takes the original panic payload
+    pub fn into_payload(self) -> Box<dyn Any + Send> {
+        let message = self.message;
+        self.payload
+            .into_inner()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .unwrap_or_else(|| Box::new(message))
+    }
+
+    /// Continues unwinding the current thread with the original panic payload
+    pub fn resume_unwind(self) -> ! {
+        std::panic::resume_unwind(self.into_payload())
+    }
+}
+
+impl fmt::Debug for PanicError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PanicError")
+            .field("message", &self.message)
+            .field("location", &self.location)
+            .finish_non_exhaustive()
+    }
+}
+
+impl fmt::Display for PanicError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.location {
+            Some(location) => write!(f, "{} at {}", self.message, location),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+impl std::error::Error for PanicError {}
+
+/// Runs `f`, capturing a panic together with its message and location
+pub(crate) fn catch_unwind<T>(f: impl FnOnce() -> T) -> Result<T, PanicError> {
+    install_hook();
+    // Discards the location of a panic which was caught elsewhere
+    let _ = last_location();
+    std::panic::catch_unwind(AssertUnwindSafe(f))
+        .map_err(|payload| PanicError::new(payload, last_location()))
+}
+
+/// Polls `fut` to completion, capturing a panic together with its message and location.
+/// The location is only known when the panic originates from the poll itself,
+/// not when it is re-raised from a task which was polled elsewhere
+#[cfg(feature = "async")]
+pub(crate) async fn catch_unwind_future<F: Future>(fut: F) -> Result<F::Output, PanicError> {
+    install_hook();
+    let mut fut = std::pin::pin!(fut);
+    // Every poll may run on a different thread
+    std::future::poll_fn(|cx| match catch_unwind(|| fut.as_mut().poll(cx)) {
+        Ok(output) => output.map(Ok),
+        Err(panic) => Poll::Ready(Err(panic)),
+    })
+    .await
+}
+
+/// Takes the location recorded by the panic hook on the current thread
+fn last_location() -> Option<String> {
+    LAST_LOCATION
+        .try_with(|location| location.borrow_mut().take())
+        .ok()
+        .flatten()
+}
+
+/// Chains a panic hook which records the location of the last panic on each thread
+fn install_hook() {
+    HOOK.call_once(|| {
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            let _ = LAST_LOCATION.try_with(|location| {
+                *location.borrow_mut() = info.location().map(ToString::to_string);
+            });
+            previous(info);
+        }));
+    });
+}
+
+/// Extracts a human-readable message from a panic payload
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(msg) = payload.downcast_ref::<&'static str>() {
+        msg.to_string()
+    } else if let Some(msg) = payload.downcast_ref::<String>() {
+        msg.clone()
+    } else {
+        "Box<dyn Any>".to_string()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::error::ProcError;
+    use crate::proc::Proc;
+    use crate::{blocking, thread};
+
+    fn expect_panic<T: std::fmt::Debug>(result: Result<T, ProcError>) -> super::PanicError {
+        match result {
+            Err(ProcError::Panicked(panic)) => panic,
+            other => panic!("expected a panic, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn blocking_panic() {
+        let panic = expect_panic(blocking(|| -> anyhow::Result<()> { panic!("boom") }).join());
+        assert_eq!(panic.message(), "boom");
+        assert!(panic.location().unwrap().starts_with(file!()));
+    }
+
+    #[test]
+    fn thread_panic() {
+        let panic = expect_panic(thread(|| -> anyhow::Result<()> { panic!("boom {}", 1) }).join());
+        assert_eq!(panic.message(), "boom 1");
+        assert!(panic.location().unwrap().starts_with(file!()));
+    }
+
+    #[test]
+    fn blocking_drop_does_not_unwind() {
+        drop(blocking(|| -> anyhow::Result<()> { panic!("boom") }));
+    }
+
+    #[test]
+    fn stale_location() {
+        let _ = std::panic::catch_unwind(|| panic!("elsewhere"));
+        let panic =
+            super::catch_unwind(|| std::panic::resume_unwind(Box::new("boom"))).unwrap_err();
+        assert_eq!(panic.message(), "boom");
+        assert_eq!(panic.location(), None);
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn future_panic() {
+        let output = futures::executor::block_on(super::catch_unwind_future(async {
+            futures::future::ready(()).await;
+            panic!("boom")
+        }));
+        let panic = output.unwrap_err();
+        assert_eq!(panic.message(), "boom");
+        assert!(panic.location().unwrap().starts_with(file!()));
+    }
+
+    #[test]
+    fn resume_unwind() {
+        let panic = expect_panic(thread(|| -> anyhow::Result<()> { panic!("boom") }).join());
+        let payload = std::panic::catch_unwind(move || panic.resume_unwind()).unwrap_err();
+        assert_eq!(payload.downcast_ref::<&str>(), Some(&"boom"));
+    }
+}
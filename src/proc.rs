@@ -1,33 +1,182 @@
-use std::ops::DerefMut;
+use crate::drop_policy::DropPolicy;
+use crate::error::{ProcError, ProcResult};
+use std::ops::{Deref, DerefMut};
+use std::task::{Poll, Waker};
+use std::time::{Duration, Instant};
+
+/// Lifecycle of a [`Proc`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProcStatus {
+    /// Not started yet, e.g. a foreground [`Proc`] which only executes when joined
+    Pending,
+    /// Executing in the background
+    Running,
+    /// Completed, the output can be joined without blocking
+    Finished,
+    /// The output was taken by a join
+    Joined,
+    /// The [`Proc`] was forgotten before it was joined
+    Forgotten,
+}
 
 /// Callable unit of execution. Similar to [`std::thread`] but enforces join-on-drop, supports
 /// combinators, and allows foreground execution.
 pub trait Proc: Send {
     type Output: Send;
-    fn join(&mut self) -> anyhow::Result<Self::Output>;
+    fn join(&mut self) -> ProcResult<Self::Output>;
+
+    /// Similar to [`Proc::join`], but gives up with [`ProcError::TimedOut`](crate::ProcError)
+    /// once the deadline has passed. A timed out [`Proc`] can be joined again later.
+    /// Defaults to timing out only if the deadline passed before the join started,
+    /// otherwise it blocks on [`Proc::join`], which ignores the deadline
+    fn join_deadline(&mut self, deadline: Instant) -> ProcResult<Self::Output> {
+        if Instant::now() >= deadline {
+            return Err(ProcError::TimedOut);
+        }
+        self.join()
+    }
+
+    /// Similar to [`Proc::join_deadline`], relative to the current time
+    fn join_timeout(&mut self, timeout: Duration) -> ProcResult<Self::Output> {
+        match Instant::now().checked_add(timeout) {
+            Some(deadline) => self.join_deadline(deadline),
+            None => self.join(),
+        }
+    }
+
+    /// Joins the [`Proc`] only if it already completed, without blocking.
+    /// Foreground units of execution never make progress here and remain pending,
+    /// as do [`Proc`]s which rely on the default [`Proc::join_deadline`]
+    fn try_join(&mut self) -> Poll<ProcResult<Self::Output>> {
+        match self.join_deadline(Instant::now()) {
+            Err(ProcError::TimedOut) => Poll::Pending,
+            output => Poll::Ready(output),
+        }
+    }
+
+    /// Lifecycle of the unit of execution. Defaults to [`ProcStatus::Running`],
+    /// as the [`Proc`] cannot tell whether it completed
+    fn status(&self) -> ProcStatus {
+        ProcStatus::Running
+    }
+
+    /// Whether the output can be joined without blocking
+    #[inline]
+    fn is_finished(&self) -> bool {
+        self.status() == ProcStatus::Finished
+    }
+
     fn forget(&mut self);
+
+    /// Sets what happens when the [`Proc`] is dropped before it was joined.
+    /// Defaults to ignoring the policy, for [`Proc`]s which do not apply one on drop
+    fn set_drop_policy(&mut self, _policy: DropPolicy) {}
+
+    /// Registers a [`Waker`] which is woken once the unit of execution completes.
+    /// Returns `false` if the [`Proc`] does not support completion notifications
+    fn register_waker(&mut self, _waker: &Waker) -> bool {
+        false
+    }
+}
+
+/// Joins a [`Proc`], bounded by an optional deadline
+pub(crate) fn join_until<P: Proc + ?Sized>(
+    proc: &mut P,
+    deadline: Option<Instant>,
+) -> ProcResult<P::Output> {
+    match deadline {
+        Some(deadline) => proc.join_deadline(deadline),
+        None => proc.join(),
+    }
 }
 
 impl<P: Proc> Proc for Box<P> {
     type Output = P::Output;
 
-    fn join(&mut self) -> anyhow::Result<Self::Output> {
+    fn join(&mut self) -> ProcResult<Self::Output> {
         self.deref_mut().join()
     }
 
+    fn join_deadline(&mut self, deadline: Instant) -> ProcResult<Self::Output> {
+        self.deref_mut().join_deadline(deadline)
+    }
+
+    fn status(&self) -> ProcStatus {
+        self.deref().status()
+    }
+
     fn forget(&mut self) {
         self.deref_mut().forget()
     }
+
+    fn set_drop_policy(&mut self, policy: DropPolicy) {
+        self.deref_mut().set_drop_policy(policy)
+    }
+
+    fn register_waker(&mut self, waker: &Waker) -> bool {
+        self.deref_mut().register_waker(waker)
+    }
 }
 
-impl<T: Send> Proc for Box<dyn Proc<Output = T>> {
+impl<T: Send> Proc for Box<dyn Proc<Output = T> + '_> {
     type Output = T;
 
-    fn join(&mut self) -> anyhow::Result<Self::Output> {
+    fn join(&mut self) -> ProcResult<Self::Output> {
         self.deref_mut().join()
     }
 
+    fn join_deadline(&mut self, deadline: Instant) -> ProcResult<Self::Output> {
+        self.deref_mut().join_deadline(deadline)
+    }
+
+    fn status(&self) -> ProcStatus {
+        self.deref().status()
+    }
+
     fn forget(&mut self) {
         self.deref_mut().forget()
     }
+
+    fn set_drop_policy(&mut self, policy: DropPolicy) {
+        self.deref_mut().set_drop_policy(policy)
+    }
+
+    fn register_waker(&mut self, waker: &Waker) -> bool {
+        self.deref_mut().register_waker(waker)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::proc_ext::ProcExt;
+
+    /// Implements only the required methods, as a downstream [`Proc`] would
+    struct Ready(Option<u64>);
+
+    impl Proc for Ready {
+        type Output = u64;
+
+        fn join(&mut self) -> ProcResult<u64> {
+            self.0.take().ok_or(ProcError::AlreadyJoined)
+        }
+
+        fn forget(&mut self) {
+            self.0 = None;
+        }
+    }
+
+    #[test]
+    fn defaults() {
+        let mut proc = Ready(Some(1)).on_drop(DropPolicy::Cancel);
+        assert_eq!(proc.status(), ProcStatus::Running);
+        // Never blocks on the join, even though it would complete right away
+        assert!(proc.try_join().is_pending());
+        assert!(matches!(
+            proc.join_timeout(Duration::ZERO),
+            Err(ProcError::TimedOut)
+        ));
+        assert!(matches!(proc.join_timeout(Duration::from_secs(1)), Ok(1)));
+        assert!(matches!(proc.join(), Err(ProcError::AlreadyJoined)));
+    }
 }
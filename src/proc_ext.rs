@@ -1,19 +1,57 @@
-use crate::combinators::{AndThenProc, OrElseProc};
+use crate::combinators::map::{AndThenResult, Context, Inspect, InspectErr, Map, MapErr};
+use crate::combinators::{
+    AdapterProc, AndThenProc, AndThenResultProc, ContextProc, FlattenProc, InspectErrProc,
+    InspectProc, MapErrProc, MapProc, OrElseProc, OrElseWithProc, ThenProc, ZipProc,
+};
+use crate::drop_policy::DropPolicy;
+use crate::error::ProcError;
+use crate::future::ProcFuture;
 use crate::proc::Proc;
+use std::fmt::Display;
 
 /// Extension trait for [`Proc`] allowing combinators over units of execution
 pub trait ProcExt: Proc + Sized {
     fn and_then<P: Proc>(self, other: P) -> AndThenProc<Self, P>;
     fn or_else<P: Proc<Output = Self::Output>>(self, other: P) -> OrElseProc<Self, P>;
-    fn boxed(self) -> Box<dyn Proc<Output = Self::Output>>;
+    fn or_else_with<P, F>(self, f: F) -> OrElseWithProc<Self, F, P>
+    where
+        P: Proc<Output = Self::Output>,
+        F: FnOnce(&ProcError) -> P + Send;
+    fn then<P, F>(self, f: F) -> ThenProc<Self, F, P>
+    where
+        P: Proc,
+        F: FnOnce(Self::Output) -> P + Send;
+    fn zip<P: Proc>(self, other: P) -> ZipProc<Self, P>;
+    fn map<U: Send, F: FnOnce(Self::Output) -> U + Send>(self, f: F) -> MapProc<Self, F>;
+    fn and_then_result<U, F>(self, f: F) -> AndThenResultProc<Self, F>
+    where
+        U: Send,
+        F: FnOnce(Self::Output) -> anyhow::Result<U> + Send;
+    fn map_err<F: FnOnce(ProcError) -> ProcError + Send>(self, f: F) -> MapErrProc<Self, F>;
+    fn inspect<F: FnOnce(&Self::Output) + Send>(self, f: F) -> InspectProc<Self, F>;
+    fn inspect_err<F: FnOnce(&ProcError) + Send>(self, f: F) -> InspectErrProc<Self, F>;
+    fn context<C: Display + Send + Sync + 'static>(self, context: C) -> ContextProc<Self, C>;
+    fn flatten(self) -> FlattenProc<Self>
+    where
+        Self::Output: Proc;
+    fn on_drop(self, policy: DropPolicy) -> Self;
+    fn join_async(self) -> ProcFuture<Self>
+    where
+        Self: 'static;
+    fn boxed<'a>(self) -> Box<dyn Proc<Output = Self::Output> + 'a>
+    where
+        Self: 'a;
 }
 
-impl<P: Proc + Send + 'static> ProcExt for P {
+impl<P: Proc + Send> ProcExt for P {
     /// Similar to [`Result::and`] but with [`Proc`]s.
+    /// The output of `self` is discarded, see [`ProcExt::then`] to pass it on.
     fn and_then<O: Proc>(self, other: O) -> AndThenProc<P, O> {
         AndThenProc {
             left: self,
             right: other,
+            left_joined: false,
+            drop_policy: DropPolicy::default(),
         }
     }
 
@@ -22,11 +60,118 @@ impl<P: Proc + Send + 'static> ProcExt for P {
         OrElseProc {
             left: self,
             right: other,
+            left_failed: false,
+            drop_policy: DropPolicy::default(),
         }
     }
 
+    /// Similar to [`Result::or_else`] but with [`Proc`]s.
+    /// The fallback [`Proc`] is only created from the error once `self` failed.
+    /// If the fallback fails as well, both errors are reported by [`ProcError::Fallback`].
+    fn or_else_with<O, F>(self, f: F) -> OrElseWithProc<P, F, O>
+    where
+        O: Proc<Output = P::Output>,
+        F: FnOnce(&ProcError) -> O + Send,
+    {
+        OrElseWithProc {
+            left: self,
+            f: Some(f),
+            right: None,
+            primary: None,
+            drop_policy: None,
+        }
+    }
+
+    /// Similar to [`Result::and_then`] but with [`Proc`]s.
+    /// The second [`Proc`] is only created once `self` completed successfully.
+    fn then<O, F>(self, f: F) -> ThenProc<P, F, O>
+    where
+        O: Proc,
+        F: FnOnce(P::Output) -> O + Send,
+    {
+        ThenProc {
+            left: self,
+            f: Some(f),
+            right: None,
+            drop_policy: None,
+        }
+    }
+
+    /// Joins both [`Proc`]s and returns both outputs, or the first error.
+    /// See [`ZipProc::fail_fast`] to stop waiting on the first error.
+    fn zip<O: Proc>(self, other: O) -> ZipProc<P, O> {
+        ZipProc {
+            left: self,
+            right: other,
+            left_output: None,
+            right_output: None,
+            fail_fast: false,
+            joined: false,
+            drop_policy: DropPolicy::default(),
+        }
+    }
+
+    /// Similar to [`Result::map`] but with [`Proc`]s.
+    fn map<U: Send, F: FnOnce(P::Output) -> U + Send>(self, f: F) -> MapProc<P, F> {
+        AdapterProc::new(self, Map(f))
+    }
+
+    /// Similar to [`Result::and_then`] but with a fallible function over the output.
+    fn and_then_result<U, F>(self, f: F) -> AndThenResultProc<P, F>
+    where
+        U: Send,
+        F: FnOnce(P::Output) -> anyhow::Result<U> + Send,
+    {
+        AdapterProc::new(self, AndThenResult(f))
+    }
+
+    /// Similar to [`Result::map_err`] but with [`Proc`]s.
+    fn map_err<F: FnOnce(ProcError) -> ProcError + Send>(self, f: F) -> MapErrProc<P, F> {
+        AdapterProc::new(self, MapErr(f))
+    }
+
+    /// Similar to [`Result::inspect`] but with [`Proc`]s.
+    fn inspect<F: FnOnce(&P::Output) + Send>(self, f: F) -> InspectProc<P, F> {
+        AdapterProc::new(self, Inspect(f))
+    }
+
+    /// Similar to [`Result::inspect_err`] but with [`Proc`]s.
+    fn inspect_err<F: FnOnce(&ProcError) + Send>(self, f: F) -> InspectErrProc<P, F> {
+        AdapterProc::new(self, InspectErr(f))
+    }
+
+    /// Similar to [`anyhow::Context::context`] but with [`Proc`]s.
+    fn context<C: Display + Send + Sync + 'static>(self, context: C) -> ContextProc<P, C> {
+        AdapterProc::new(self, Context(context))
+    }
+
+    /// Joins the [`Proc`] returned by `self`.
+    fn flatten(self) -> FlattenProc<P>
+    where
+        P::Output: Proc,
+    {
+        self.then(std::convert::identity as fn(_) -> _)
+    }
+
+    /// Sets what happens when the [`Proc`] is dropped before it was joined
+    fn on_drop(mut self, policy: DropPolicy) -> Self {
+        self.set_drop_policy(policy);
+        self
+    }
+
+    /// Allows the [`Proc`] to be awaited from async code without blocking the executor
+    fn join_async(self) -> ProcFuture<Self>
+    where
+        Self: 'static,
+    {
+        ProcFuture::new(self)
+    }
+
     /// Provides dynamic dispatch for [`Proc`]
-    fn boxed(self) -> Box<dyn Proc<Output = Self::Output>> {
+    fn boxed<'a>(self) -> Box<dyn Proc<Output = Self::Output> + 'a>
+    where
+        Self: 'a,
+    {
         Box::new(self)
     }
 }
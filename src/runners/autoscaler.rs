@@ -0,0 +1,131 @@
+use crate::runners::builder::ThreadBuilder;
+use crate::runners::pool::PoolHandle;
+use crate::runners::thread::NativeThread;
+use std::io;
+use std::time::Duration;
+
+const DEFAULT_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Adjusts the number of workers of a [`WorkerPool`](crate::WorkerPool) between `min` and `max`.
+/// Every interval, a worker is added when the queued items would wait longer than the target
+/// latency, given the average time it took to process an item. A worker is removed when nothing
+/// is queued and the workers were busy for less than half of the interval
+#[derive(Debug, Clone)]
+pub struct Autoscaler {
+    min: usize,
+    max: usize,
+    interval: Duration,
+    target_latency: Option<Duration>,
+}
+
+impl Autoscaler {
+    pub fn new(min: usize, max: usize) -> Self {
+        assert!(min >= 1 && min <= max);
+        Self {
+            min,
+            max,
+            interval: DEFAULT_INTERVAL,
+            target_latency: None,
+        }
+    }
+
+    /// How often the pool is resized, which defaults to 100ms
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// How long items may be queued before a worker is added, which defaults to the interval
+    pub fn target_latency(mut self, target_latency: Duration) -> Self {
+        self.target_latency = Some(target_latency);
+        self
+    }
+
+    #[inline]
+    pub(crate) fn max(&self) -> usize {
+        self.max
+    }
+
+    #[inline]
+    pub(crate) fn clamp(&self, workers: usize) -> usize {
+        workers.clamp(self.min, self.max)
+    }
+
+    /// Resizes the pool every interval, until the thread is cancelled
+    pub(crate) fn spawn(
+        self,
+        threads: ThreadBuilder,
+        pool: PoolHandle,
+    ) -> io::Result<NativeThread<()>> {
+        threads.spawn_cancellable(move |token| {
+            while !token.wait_timeout(self.interval) {
+                let (busy, completed) = pool.sample();
+                let workers = pool.workers();
+                let target = self.scale(workers, pool.queued(), busy, completed);
+                if target != workers {
+                    pool.resize(target)?;
+                }
+            }
+            Ok(())
+        })
+    }
+
+    /// Number of workers for the next interval, given the time the workers
+    /// were busy with the items they completed during the last one
+    fn scale(&self, workers: usize, queued: usize, busy: Duration, completed: u64) -> usize {
+        let target_latency = self.target_latency.unwrap_or(self.interval);
+        let wait = match u32::try_from(completed)
+            .ok()
+            .and_then(|n| busy.checked_div(n))
+        {
+            Some(latency) => {
+                latency.saturating_mul(u32::try_from(queued).unwrap_or(u32::MAX)) / workers as u32
+            }
+            // Nothing completed, so the workers are either idle or stuck on slow items
+            None if queued > 0 => Duration::MAX,
+            None => Duration::ZERO,
+        };
+        let capacity = self.interval.saturating_mul(workers as u32);
+        if wait > target_latency && workers < self.max {
+            workers + 1
+        } else if queued == 0 && busy < capacity / 2 && workers > self.min {
+            workers - 1
+        } else {
+            self.clamp(workers)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const MS: Duration = Duration::from_millis(1);
+
+    #[test]
+    fn scales_up_on_backlog() {
+        let autoscaler = Autoscaler::new(1, 4).interval(100 * MS);
+        // 10 queued items of 20ms each take 100ms on 2 workers
+        assert_eq!(autoscaler.scale(2, 10, 200 * MS, 10), 2);
+        assert_eq!(autoscaler.scale(2, 11, 200 * MS, 10), 3);
+        assert_eq!(autoscaler.scale(2, 1, Duration::ZERO, 0), 3);
+        assert_eq!(autoscaler.scale(4, 100, 200 * MS, 10), 4);
+    }
+
+    #[test]
+    fn scales_down_when_idle() {
+        let autoscaler = Autoscaler::new(1, 4).interval(100 * MS);
+        assert_eq!(autoscaler.scale(2, 0, 99 * MS, 10), 1);
+        assert_eq!(autoscaler.scale(2, 0, 100 * MS, 10), 2);
+        assert_eq!(autoscaler.scale(2, 1, 10 * MS, 10), 2);
+        assert_eq!(autoscaler.scale(1, 0, Duration::ZERO, 0), 1);
+    }
+
+    #[test]
+    fn target_latency() {
+        let autoscaler = Autoscaler::new(1, 4)
+            .interval(100 * MS)
+            .target_latency(10 * MS);
+        assert_eq!(autoscaler.scale(2, 2, 200 * MS, 10), 3);
+    }
+}
@@ -0,0 +1,180 @@
+use crate::cancel::CancellationToken;
+use crate::runners::thread::NativeThread;
+use std::io;
+
+/// Configuration for spawning a [`NativeThread`], similar to [`std::thread::Builder`]
+#[derive(Debug, Clone, Default)]
+pub struct ThreadBuilder {
+    pub(crate) name: Option<String>,
+    stack_size: Option<usize>,
+    affinity: Option<Vec<usize>>,
+    nice: Option<i32>,
+}
+
+impl ThreadBuilder {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Names the thread, as shown in panic messages, debuggers and profilers
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Size of the thread's stack in bytes
+    pub fn stack_size(mut self, size: usize) -> Self {
+        self.stack_size = Some(size);
+        self
+    }
+
+    /// Pins the thread to the given CPU cores. Only supported on Linux, ignored elsewhere.
+    /// Spawning fails with [`io::ErrorKind::InvalidInput`] for cores beyond `CPU_SETSIZE`
+    pub fn affinity(mut self, cores: impl IntoIterator<Item = usize>) -> Self {
+        self.affinity = Some(cores.into_iter().collect());
+        self
+    }
+
+    /// Scheduling priority of the thread, from -20 (highest) to 19 (lowest).
+    /// Only supported on Linux, ignored elsewhere
+    pub fn nice(mut self, nice: i32) -> Self {
+        self.nice = Some(nice);
+        self
+    }
+
+    /// Executes a function to completion on a native OS thread.
+    /// Failing to apply the affinity or priority fails the [`Proc`](crate::Proc).
+    pub fn spawn<F, T>(self, f: F) -> io::Result<NativeThread<T>>
+    where
+        F: FnOnce() -> anyhow::Result<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        self.spawn_cancellable(move |_| f())
+    }
+
+    /// Executes a function to completion on a native OS thread.
+    /// The function receives a [`CancellationToken`] which is cancelled when the
+    /// [`Proc`](crate::Proc) is forgotten
+    pub fn spawn_cancellable<F, T>(self, f: F) -> io::Result<NativeThread<T>>
+    where
+        F: FnOnce(CancellationToken) -> anyhow::Result<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let mut builder = std::thread::Builder::new();
+        if let Some(name) = self.name {
+            builder = builder.name(name);
+        }
+        if let Some(size) = self.stack_size {
+            builder = builder.stack_size(size);
+        }
+        if let Some(cores) = &self.affinity {
+            check_affinity(cores)?;
+        }
+        let (affinity, nice) = (self.affinity, self.nice);
+        NativeThread::spawn_with(builder, move |token| {
+            if let Some(cores) = affinity {
+                set_affinity(&cores)?;
+            }
+            if let Some(nice) = nice {
+                set_nice(nice)?;
+            }
+            f(token)
+        })
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn check_affinity(cores: &[usize]) -> io::Result<()> {
+    let max = libc::CPU_SETSIZE as usize;
+    match cores.iter().find(|core| **core >= max) {
+        Some(core) => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("CPU core {core} exceeds the maximum of {}", max - 1),
+        )),
+        None => Ok(()),
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn check_affinity(_cores: &[usize]) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+fn set_affinity(cores: &[usize]) -> io::Result<()> {
+    // SAFETY: `cpu_set_t` is a plain bitmask, and is only passed along with its own size
+    unsafe {
+        let mut set = std::mem::zeroed::<libc::cpu_set_t>();
+        for core in cores {
+            libc::CPU_SET(*core, &mut set);
+        }
+        if libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn set_affinity(_cores: &[usize]) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+fn set_nice(nice: i32) -> io::Result<()> {
+    // SAFETY: On Linux, `setpriority` with a thread id only affects the calling thread
+    unsafe {
+        let tid = libc::gettid() as libc::id_t;
+        if libc::setpriority(libc::PRIO_PROCESS, tid, nice) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn set_nice(_nice: i32) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::proc::Proc;
+
+    #[test]
+    fn named() {
+        let name = ThreadBuilder::new()
+            .name("circuit-test")
+            .stack_size(256 * 1024)
+            .spawn(|| Ok(std::thread::current().name().map(ToString::to_string)))
+            .expect("could not spawn")
+            .join()
+            .expect("could not join");
+        assert_eq!(name.as_deref(), Some("circuit-test"));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn pinned() {
+        let core = unsafe { libc::sched_getcpu() } as usize;
+        let pinned = ThreadBuilder::new()
+            .affinity([core])
+            .nice(1)
+            .spawn(move || Ok(unsafe { libc::sched_getcpu() } as usize))
+            .expect("could not spawn")
+            .join()
+            .expect("could not join");
+        assert_eq!(pinned, core);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn invalid_affinity() {
+        let spawned = ThreadBuilder::new()
+            .affinity([libc::CPU_SETSIZE as usize])
+            .spawn(|| Ok(()));
+        assert!(matches!(spawned, Err(err) if err.kind() == io::ErrorKind::InvalidInput));
+    }
+}
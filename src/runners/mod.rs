@@ -1,9 +1,31 @@
 #[cfg(feature = "tokio")]
+mod autoscaler;
+#[cfg(feature = "tokio")]
 mod pool;
 #[cfg(feature = "tokio")]
-pub use pool::with_worker_pool;
+pub use autoscaler::Autoscaler;
+#[cfg(feature = "tokio")]
+pub use pool::{
+    with_worker_pool, with_worker_pool_builder, AsyncWorkerPoolBuilder, DeadLetter, OutputOrder,
+    PoolHandle, Reply, WorkerPool, WorkerPoolBuilder,
+};
 
+mod builder;
+mod process;
+#[cfg(feature = "async")]
 pub mod runtime;
+mod scope;
+#[cfg(feature = "async")]
+mod spawner;
 mod thread;
 
-pub use thread::NativeThread;
+pub use builder::ThreadBuilder;
+pub use process::{ChildProcess, CommandError};
+pub use scope::Scope;
+#[cfg(feature = "smol")]
+pub use spawner::SmolSpawner;
+#[cfg(feature = "tokio")]
+pub use spawner::TokioSpawner;
+#[cfg(feature = "async")]
+pub use spawner::{Spawner, TaskHandle};
+pub use thread::{NativeThread, ScopedThread, ThreadHandle};
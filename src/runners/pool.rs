@@ -1,58 +1,1412 @@
-use crate::proc::Proc;
+use crate::combinators::JoinTasks;
+use crate::drop_policy::DropPolicy;
+use crate::error::{ProcError, ProcResult};
+use crate::panic::{catch_unwind, catch_unwind_future};
+use crate::proc::{join_until, Proc, ProcStatus};
 use crate::proc_ext::ProcExt;
-use crate::{thread, tokio};
-use flume::{bounded, Receiver, Sender};
+use crate::runners::autoscaler::Autoscaler;
+use crate::runners::builder::ThreadBuilder;
+use crate::runners::runtime::TaskRuntime;
+use crate::runners::thread::NativeThread;
+use crate::{blocking, tokio};
+use ::tokio::sync::Semaphore;
+use flume::{bounded, unbounded, Receiver, SendError, Sender};
+use futures::future::{select, Either};
+use std::collections::VecDeque;
+use std::future::Future;
+use std::io;
+use std::panic::AssertUnwindSafe;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::task::Poll;
+use std::time::{Duration, Instant};
 
+/// Order in which a worker pool delivers the outputs of its items
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputOrder {
+    /// Outputs are delivered in the order the items were received.
+    /// A slow item holds back the outputs of the items after it
+    #[default]
+    Ordered,
+    /// Outputs are delivered as soon as they are completed
+    Unordered,
+    /// Similar to [`OutputOrder::Ordered`], but at most this many items are in flight
+    /// behind the oldest undelivered one, bounding the outputs held back by a slow item
+    Window(usize),
+}
+
+/// Handle to deliver the output for an item of a worker pool
+pub struct Reply<O> {
+    seq: usize,
+    results: Sender<(usize, Option<O>)>,
+    sent: bool,
+}
+
+impl<O> Reply<O> {
+    /// Delivers the output for the item. Dropping the [`Reply`] instead skips the item
+    pub fn send(mut self, output: O) -> Result<(), SendError<O>> {
+        self.sent = true;
+        self.results
+            .send((self.seq, Some(output)))
+            .map_err(|SendError((_, output))| SendError(output.unwrap()))
+    }
+}
+
+impl<O> Drop for Reply<O> {
+    fn drop(&mut self) {
+        if !self.sent {
+            let _ = self.results.send((self.seq, None));
+        }
+    }
+}
+
+/// Processes items from `in_r` on `workers` native threads named `pool-<id>`,
+/// delivering their outputs to `out_s` in the order the items were received
 pub fn with_worker_pool<I, O, F>(
     workers: usize,
     channel_capacity: usize,
     in_r: Receiver<I>,
     out_s: Sender<O>,
     work_fn: F,
-) -> impl Proc
+) -> impl Proc<Output = ()>
 where
     I: Send + 'static,
     O: Send + 'static,
-    F: Fn(usize, Receiver<(I, Sender<O>)>) + Copy + Send + 'static,
+    F: Fn(usize, Receiver<(I, Reply<O>)>) + Copy + Send + 'static,
 {
-    assert!(workers >= 1);
-    let (work_dispatch_s, work_dispatch_r) = bounded(channel_capacity);
-    let (work_collect_s, work_collect_r) = bounded(channel_capacity);
-    let dispatch = tokio(async move {
-        // dispatch work to workers
-        let dispatch = tokio::spawn(async move {
-            while let Ok(msg) = in_r.recv_async().await {
-                let (s, r) = flume::bounded(1);
-                if work_collect_s.send_async(r).await.is_err() {
-                    break;
-                }
-                if work_dispatch_s.send_async((msg, s)).await.is_err() {
-                    break;
+    with_worker_pool_builder(
+        ThreadBuilder::new().name("pool"),
+        workers,
+        channel_capacity,
+        in_r,
+        out_s,
+        work_fn,
+    )
+}
+
+/// Similar to [`with_worker_pool`], but spawns the workers from `threads`.
+/// The name of the builder is used as a prefix, naming the workers `<name>-<id>`.
+/// See [`WorkerPoolBuilder::order`] to deliver the outputs in another order
+pub fn with_worker_pool_builder<I, O, F>(
+    threads: ThreadBuilder,
+    workers: usize,
+    channel_capacity: usize,
+    in_r: Receiver<I>,
+    out_s: Sender<O>,
+    work_fn: F,
+) -> impl Proc<Output = ()>
+where
+    I: Send + 'static,
+    O: Send + 'static,
+    F: Fn(usize, Receiver<(I, Reply<O>)>) + Copy + Send + 'static,
+{
+    spawn_pool(
+        threads,
+        OutputOrder::Ordered,
+        workers,
+        channel_capacity,
+        in_r,
+        out_s,
+        move |worker_id, work_r| {
+            move || {
+                work_fn(worker_id, work_r);
+                Ok(())
+            }
+        },
+    )
+}
+
+type Init<S> = Arc<dyn Fn(usize) -> anyhow::Result<S> + Send + Sync>;
+type Teardown<S> = Arc<dyn Fn(usize, S) + Send + Sync>;
+// Failed items are cloned up front, since the work function consumes them
+type DeadLetters<I> = (Sender<DeadLetter<I>>, fn(&I) -> I);
+
+/// Configuration for a worker pool in which every worker owns a state,
+/// e.g. a connection, created when it starts and torn down when it stops
+pub struct WorkerPoolBuilder<S> {
+    threads: ThreadBuilder,
+    order: OutputOrder,
+    workers: usize,
+    channel_capacity: usize,
+    max_restarts: usize,
+    autoscaler: Option<Autoscaler>,
+    init: Init<S>,
+    teardown: Teardown<S>,
+}
+
+impl WorkerPoolBuilder<()> {
+    /// Configures a pool of `workers` native threads named `pool-<id>`, which have no state.
+    /// See [`WorkerPoolBuilder::with_state`] for workers owning a state
+    pub fn new(workers: usize) -> Self {
+        Self::with_state(workers, |_| Ok(()))
+    }
+}
+
+impl<S: 'static> WorkerPoolBuilder<S> {
+    /// Configures a pool of `workers` native threads named `pool-<id>`, which create their
+    /// state from their id with `init`, when they start or restart.
+    /// A failure stops the worker, failing the pool
+    pub fn with_state(
+        workers: usize,
+        init: impl Fn(usize) -> anyhow::Result<S> + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            threads: ThreadBuilder::new().name("pool"),
+            order: OutputOrder::Ordered,
+            workers,
+            channel_capacity: workers,
+            max_restarts: 0,
+            autoscaler: None,
+            init: Arc::new(init),
+            teardown: Arc::new(|_, _| ()),
+        }
+    }
+
+    /// Spawns the workers from `threads`. The name of the builder is used as a prefix,
+    /// naming the workers `<name>-<id>`
+    pub fn threads(mut self, threads: ThreadBuilder) -> Self {
+        self.threads = threads;
+        self
+    }
+
+    /// Order in which the outputs are delivered, which defaults to [`OutputOrder::Ordered`]
+    pub fn order(mut self, order: OutputOrder) -> Self {
+        self.order = order;
+        self
+    }
+
+    /// Capacity of the channel dispatching items to the workers, which defaults to the number of workers
+    pub fn channel_capacity(mut self, channel_capacity: usize) -> Self {
+        self.channel_capacity = channel_capacity;
+        self
+    }
+
+    /// Number of times the workers may be restarted after they failed, which defaults to none.
+    /// A failure beyond that stops the worker, failing the pool
+    pub fn max_restarts(mut self, max_restarts: usize) -> Self {
+        self.max_restarts = max_restarts;
+        self
+    }
+
+    /// Resizes the pool while it runs, within the bounds of the [`Autoscaler`]
+    pub fn autoscale(mut self, autoscaler: Autoscaler) -> Self {
+        self.autoscaler = Some(autoscaler);
+        self
+    }
+
+    /// Consumes the state of a worker when it stops, either because the input closed,
+    /// it failed, even past [`WorkerPoolBuilder::max_restarts`], or the pool shrunk.
+    /// Does not run when `init` failed, since there is no state to consume
+    pub fn teardown(mut self, teardown: impl Fn(usize, S) + Send + Sync + 'static) -> Self {
+        self.teardown = Arc::new(teardown);
+        self
+    }
+
+    /// Processes items from `in_r` on the workers, delivering their outputs to `out_s`.
+    /// Every worker runs its own copy of `work_fn`. An error or panic skips the item and
+    /// restarts the worker with a fresh state, see [`WorkerPoolBuilder::max_restarts`]
+    pub fn spawn<I, O, F>(self, in_r: Receiver<I>, out_s: Sender<O>, work_fn: F) -> WorkerPool
+    where
+        I: Send + 'static,
+        O: Send + 'static,
+        F: FnMut(&mut S, I) -> anyhow::Result<O> + Clone + Send + 'static,
+    {
+        self.spawn_with(in_r, out_s, None, work_fn)
+    }
+
+    /// Similar to [`WorkerPoolBuilder::spawn`], but reports the items on which a worker
+    /// failed to `dead_letters`, along with their error. Once the restarts are exhausted,
+    /// the last item is still reported and the pool fails with a summary of the error.
+    /// Since `work_fn` consumes its item, every item is cloned before it is processed,
+    /// even when it succeeds. Keep items cheap to clone, e.g. by wrapping them in an [`Arc`]
+    pub fn spawn_supervised<I, O, F>(
+        self,
+        in_r: Receiver<I>,
+        out_s: Sender<O>,
+        dead_letters: Sender<DeadLetter<I>>,
+        work_fn: F,
+    ) -> WorkerPool
+    where
+        I: Clone + Send + 'static,
+        O: Send + 'static,
+        F: FnMut(&mut S, I) -> anyhow::Result<O> + Clone + Send + 'static,
+    {
+        self.spawn_with(in_r, out_s, Some((dead_letters, I::clone)), work_fn)
+    }
+
+    fn spawn_with<I, O, F>(
+        self,
+        in_r: Receiver<I>,
+        out_s: Sender<O>,
+        dead_letters: Option<DeadLetters<I>>,
+        work_fn: F,
+    ) -> WorkerPool
+    where
+        I: Send + 'static,
+        O: Send + 'static,
+        F: FnMut(&mut S, I) -> anyhow::Result<O> + Clone + Send + 'static,
+    {
+        let Self {
+            threads,
+            order,
+            workers,
+            channel_capacity,
+            max_restarts,
+            autoscaler,
+            init,
+            teardown,
+        } = self;
+        let workers = autoscaler
+            .as_ref()
+            .map_or(workers, |autoscaler| autoscaler.clamp(workers));
+        if workers == 0 {
+            return WorkerPool::failed(no_workers());
+        }
+        let max_workers = autoscaler.as_ref().map_or(workers, Autoscaler::max);
+        // Items waiting for a worker, either in the input or the dispatch channel
+        let backlog = in_r.clone();
+        let (dispatch, work_r, window) =
+            dispatch(order, max_workers, channel_capacity, in_r, out_s);
+        // Unless it is fixed, the window grows along with the pool
+        let window = (!matches!(order, OutputOrder::Window(_))).then_some(window);
+        let (retire_s, retire_r) = unbounded();
+        let stats = Arc::new(Stats::default());
+        let worker_threads = threads.clone();
+        let pool = Arc::new_cyclic(|pool: &Weak<PoolState>| {
+            let (pool, queue) = (pool.clone(), work_r.clone());
+            let (retired, worker_stats) = (retire_r.clone(), stats.clone());
+            // Every worker takes a copy of `work_fn`, which need not be `Sync` itself
+            let work_fn = Mutex::new(work_fn);
+            let spawn = move |worker_id| {
+                let mut work_fn = work_fn.lock().unwrap().clone();
+                let (init, teardown) = (init.clone(), teardown.clone());
+                let (work_r, retire_r) = (work_r.clone(), retired.clone());
+                let (dead_letters, stats) = (dead_letters.clone(), worker_stats.clone());
+                let live = Live::enter(&pool);
+                let mut builder = worker_threads.clone();
+                builder.name = builder.name.map(|prefix| format!("{prefix}-{worker_id}"));
+                builder.spawn(move || {
+                    let _live = live;
+                    // Stops once retired, or when the input closed. Blocks on both at once,
+                    // such that idle workers do not wake up until either happens
+                    let next = || {
+                        let retired = std::pin::pin!(async {
+                            if retire_r.recv_async().await.is_err() {
+                                // Without a pool to retire it, only the input stops the worker
+                                std::future::pending::<()>().await;
+                            }
+                        });
+                        let item = std::pin::pin!(work_r.recv_async());
+                        match futures::executor::block_on(select(retired, item)) {
+                            Either::Left(_) => None,
+                            Either::Right((item, _)) => item.ok(),
+                        }
+                    };
+                    loop {
+                        let mut state = init(worker_id)?;
+                        let failure = std::iter::from_fn(next).find_map(|(item, reply)| {
+                            let kept = dead_letters.as_ref().map(|(_, clone)| clone(&item));
+                            let started = Instant::now();
+                            let output =
+                                catch_unwind(AssertUnwindSafe(|| work_fn(&mut state, item)));
+                            stats.record(started.elapsed());
+                            let error = match output {
+                                Ok(Ok(output)) => {
+                                    let _ = reply.send(output);
+                                    return None;
+                                }
+                                Ok(Err(err)) => ProcError::from(err),
+                                Err(panic) => ProcError::from(panic),
+                            };
+                            Some((kept, error))
+                        });
+                        teardown(worker_id, state);
+                        let Some((item, error)) = failure else {
+                            return Ok(());
+                        };
+                        let restarted = stats.restarts.fetch_update(
+                            Ordering::Relaxed,
+                            Ordering::Relaxed,
+                            |n| (n < max_restarts).then_some(n + 1),
+                        );
+                        // Reported before giving up as well, such that no failed item is lost
+                        let error = match (&dead_letters, item) {
+                            (Some((dead_letters, _)), Some(item)) => {
+                                let exhausted = anyhow::anyhow!(
+                                    "worker {worker_id} exhausted its restarts: {error}"
+                                );
+                                let _ = dead_letters.send(DeadLetter {
+                                    item,
+                                    worker_id,
+                                    error,
+                                });
+                                ProcError::from(exhausted)
+                            }
+                            _ => error,
+                        };
+                        if restarted.is_err() {
+                            return Err(error.resume_if_panicked().into());
+                        }
+                    }
+                })
+            };
+            PoolState {
+                workers: Mutex::new(Workers {
+                    threads: Vec::new(),
+                    target: 0,
+                    peak: max_workers,
+                    next_id: 0,
+                    factory: Some(Factory {
+                        spawn: Box::new(spawn),
+                        queued: Box::new(move || queue.len() + backlog.len()),
+                        window,
+                    }),
+                    failure: None,
+                    drop_policy: DropPolicy::default(),
+                }),
+                retire: (retire_s, retire_r),
+                stats,
+            }
+        });
+        let handle = PoolHandle(pool);
+        if let Err(err) = handle.resize(workers) {
+            handle.0.fail(anyhow::Error::from(err).into());
+        }
+        let autoscaler = autoscaler.and_then(|autoscaler| {
+            let mut builder = threads;
+            builder.name = builder.name.map(|prefix| format!("{prefix}-autoscaler"));
+            match autoscaler.spawn(builder, handle.clone()) {
+                Ok(autoscaler) => Some(autoscaler),
+                Err(err) => {
+                    handle.0.fail(anyhow::Error::from(err).into());
+                    None
                 }
             }
         });
-        // collect output from workers
-        let collect = tokio::spawn(async move {
-            while let Ok(r) = work_collect_r.recv_async().await {
-                if let Ok(output) = r.recv_async().await {
-                    if out_s.send_async(output).await.is_err() {
-                        break;
+        let mut dispatch = dispatch;
+        WorkerPool {
+            dispatch: Some(crate::thread(move || Ok(dispatch.join()?))),
+            autoscaler,
+            handle,
+            settled: None,
+            drop_policy: DropPolicy::default(),
+        }
+    }
+}
+
+/// Configuration for a worker pool which processes items with an async function on a
+/// [`TaskRuntime`]. Its workers are tasks rather than threads, so they have no state,
+/// are never restarted and cannot be resized
+pub struct AsyncWorkerPoolBuilder {
+    order: OutputOrder,
+    concurrency: usize,
+    channel_capacity: usize,
+}
+
+impl AsyncWorkerPoolBuilder {
+    /// Configures a pool which processes at most `concurrency` items at a time
+    pub fn new(concurrency: usize) -> Self {
+        Self {
+            order: OutputOrder::Ordered,
+            concurrency,
+            channel_capacity: concurrency,
+        }
+    }
+
+    /// Order in which the outputs are delivered, which defaults to [`OutputOrder::Ordered`]
+    pub fn order(mut self, order: OutputOrder) -> Self {
+        self.order = order;
+        self
+    }
+
+    /// Capacity of the channel dispatching items to the workers, which defaults to the concurrency
+    pub fn channel_capacity(mut self, channel_capacity: usize) -> Self {
+        self.channel_capacity = channel_capacity;
+        self
+    }
+
+    /// Processes items from `in_r` with `work_fn` on `runtime`, delivering their outputs
+    /// to `out_s`. A panic skips the item, the worker carries on with the next one
+    pub fn spawn<I, O, F, Fut>(
+        self,
+        runtime: &TaskRuntime,
+        in_r: Receiver<I>,
+        out_s: Sender<O>,
+        work_fn: F,
+    ) -> impl Proc<Output = ()>
+    where
+        I: Send + 'static,
+        O: Send + 'static,
+        F: Fn(I) -> Fut + Clone + Send + 'static,
+        Fut: Future<Output = O> + Send + 'static,
+    {
+        let Self {
+            order,
+            concurrency,
+            channel_capacity,
+        } = self;
+        if concurrency == 0 {
+            return blocking(|| Err(no_workers().into())).boxed();
+        }
+        let (dispatch, work_r, _) = dispatcher(order, concurrency, channel_capacity, in_r, out_s);
+        let tasks = JoinTasks::with_task_runtime(runtime.share()).and(dispatch);
+        (0..concurrency)
+            .fold(tasks, |tasks, _| {
+                let (work_r, work_fn) = (work_r.clone(), work_fn.clone());
+                tasks.and(async move {
+                    while let Ok((item, reply)) = work_r.recv_async().await {
+                        // Dropping the reply skips the item
+                        if let Ok(output) = catch_unwind_future(work_fn(item)).await {
+                            let _ = reply.send(output);
+                        }
                     }
+                })
+            })
+            .map(|_| ())
+            .boxed()
+    }
+}
+
+/// Item on which a worker of a pool failed, see [`WorkerPoolBuilder::spawn_supervised`]
+#[derive(Debug)]
+pub struct DeadLetter<I> {
+    item: I,
+    worker_id: usize,
+    error: ProcError,
+}
+
+impl<I> DeadLetter<I> {
+    #[inline]
+    pub fn item(&self) -> &I {
+        &self.item
+    }
+
+    #[inline]
+    pub fn into_item(self) -> I {
+        self.item
+    }
+
+    #[inline]
+    pub fn worker_id(&self) -> usize {
+        self.worker_id
+    }
+
+    /// The error returned by the worker, or its panic
+    #[inline]
+    pub fn error(&self) -> &ProcError {
+        &self.error
+    }
+}
+
+type SpawnWorker = Box<dyn Fn(usize) -> io::Result<NativeThread<()>> + Send + Sync>;
+
+/// Spawns the workers of a [`WorkerPool`]
+struct Factory {
+    spawn: SpawnWorker,
+    queued: Box<dyn Fn() -> usize + Send + Sync>,
+    window: Option<Arc<Semaphore>>,
+}
+
+struct Workers {
+    threads: Vec<NativeThread<()>>,
+    // Number of workers the pool is sized to, excluding those asked to retire
+    target: usize,
+    // Largest number of workers the window of the dispatcher was sized for
+    peak: usize,
+    next_id: usize,
+    // Dropped once the pool takes no new workers, which lets the dispatcher
+    // give up once all workers stopped
+    factory: Option<Factory>,
+    failure: Option<ProcError>,
+    drop_policy: DropPolicy,
+}
+
+#[derive(Default)]
+struct Stats {
+    live: AtomicUsize,
+    restarts: AtomicUsize,
+    // Time spent on the items completed since the last sample
+    busy: AtomicU64,
+    completed: AtomicU64,
+}
+
+impl Stats {
+    fn record(&self, elapsed: Duration) {
+        let elapsed = u64::try_from(elapsed.as_nanos()).unwrap_or(u64::MAX);
+        self.busy.fetch_add(elapsed, Ordering::Relaxed);
+        self.completed.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+struct PoolState {
+    workers: Mutex<Workers>,
+    // Carries a message for every worker which is asked to retire
+    retire: (Sender<()>, Receiver<()>),
+    stats: Arc<Stats>,
+}
+
+impl PoolState {
+    /// Stops taking new workers
+    fn close(&self) {
+        self.workers.lock().unwrap().factory = None;
+    }
+
+    fn fail(&self, err: ProcError) {
+        self.workers.lock().unwrap().failure.get_or_insert(err);
+    }
+
+    /// Joins the workers, reporting the first failure
+    fn join_workers(&self, deadline: Option<Instant>) -> ProcResult<()> {
+        let threads = std::mem::take(&mut self.workers.lock().unwrap().threads);
+        let mut threads = threads.into_iter();
+        while let Some(mut worker) = threads.next() {
+            match join_until(&mut worker, deadline) {
+                Ok(()) => {}
+                Err(ProcError::TimedOut) => {
+                    let mut workers = self.workers.lock().unwrap();
+                    workers
+                        .threads
+                        .extend(std::iter::once(worker).chain(threads));
+                    return Err(ProcError::TimedOut);
+                }
+                Err(err) => self.fail(err),
+            }
+        }
+        self.workers
+            .lock()
+            .unwrap()
+            .failure
+            .take()
+            .map_or(Ok(()), Err)
+    }
+}
+
+/// Tracks a running worker. Once none are left, the pool is closed
+struct Live(Weak<PoolState>);
+
+impl Live {
+    fn enter(pool: &Weak<PoolState>) -> Self {
+        if let Some(pool) = pool.upgrade() {
+            pool.stats.live.fetch_add(1, Ordering::Relaxed);
+        }
+        Self(pool.clone())
+    }
+}
+
+impl Drop for Live {
+    fn drop(&mut self) {
+        if let Some(pool) = self.0.upgrade() {
+            if pool.stats.live.fetch_sub(1, Ordering::Relaxed) == 1 {
+                pool.close();
+            }
+        }
+    }
+}
+
+/// Handle to resize a running [`WorkerPool`]
+#[derive(Clone)]
+pub struct PoolHandle(Arc<PoolState>);
+
+impl PoolHandle {
+    /// Number of workers the pool is sized to
+    pub fn workers(&self) -> usize {
+        self.0.workers.lock().unwrap().target
+    }
+
+    /// Number of items waiting for a worker, including those not taken from the input yet
+    pub fn queued(&self) -> usize {
+        let workers = self.0.workers.lock().unwrap();
+        workers
+            .factory
+            .as_ref()
+            .map_or(0, |factory| (factory.queued)())
+    }
+
+    /// Number of times a worker was restarted after it failed
+    #[inline]
+    pub fn restarts(&self) -> usize {
+        self.0.stats.restarts.load(Ordering::Relaxed)
+    }
+
+    /// Grows or shrinks the pool. Retiring workers finish their current item first.
+    /// Does nothing once the pool stopped taking items
+    pub fn resize(&self, workers: usize) -> io::Result<()> {
+        if workers == 0 {
+            return Err(no_workers());
+        }
+        let mut state = self.0.workers.lock().unwrap();
+        let state = &mut *state;
+        let Some(factory) = &state.factory else {
+            return Ok(());
+        };
+        // Releases retired workers, keeping the first failure
+        state.threads.retain_mut(|worker| match worker.try_join() {
+            Poll::Pending => true,
+            Poll::Ready(output) => {
+                if let Err(err) = output {
+                    state.failure.get_or_insert(err);
                 }
+                false
             }
         });
-        dispatch.await?;
-        collect.await?;
+        while state.target > workers {
+            let _ = self.0.retire.0.send(());
+            state.target -= 1;
+        }
+        while state.target < workers {
+            // Takes back a retirement which was not picked up yet, before adding a worker
+            if self.0.retire.1.try_recv().is_err() {
+                let mut worker = (factory.spawn)(state.next_id)?;
+                worker.set_drop_policy(state.drop_policy);
+                state.threads.push(worker);
+                state.next_id += 1;
+            }
+            state.target += 1;
+        }
+        if let Some(window) = &factory.window {
+            window.add_permits(workers.saturating_sub(state.peak));
+        }
+        state.peak = state.peak.max(workers);
         Ok(())
-    });
+    }
+
+    /// Takes the time the workers spent on the items they completed since the last sample
+    pub(crate) fn sample(&self) -> (Duration, u64) {
+        let stats = &self.0.stats;
+        let busy = Duration::from_nanos(stats.busy.swap(0, Ordering::Relaxed));
+        (busy, stats.completed.swap(0, Ordering::Relaxed))
+    }
+}
+
+/// Handle to a running worker pool, which completes once its input closed
+/// and all outputs were delivered
+pub struct WorkerPool {
+    dispatch: Option<NativeThread<()>>,
+    autoscaler: Option<NativeThread<()>>,
+    handle: PoolHandle,
+    settled: Option<ProcStatus>,
+    drop_policy: DropPolicy,
+}
+
+impl WorkerPool {
+    /// Pool which failed to start, reporting the error once joined
+    fn failed(err: io::Error) -> Self {
+        let pool = PoolState {
+            workers: Mutex::new(Workers {
+                threads: Vec::new(),
+                target: 0,
+                peak: 0,
+                next_id: 0,
+                factory: None,
+                failure: Some(anyhow::Error::from(err).into()),
+                drop_policy: DropPolicy::default(),
+            }),
+            retire: unbounded(),
+            stats: Default::default(),
+        };
+        Self {
+            dispatch: None,
+            autoscaler: None,
+            handle: PoolHandle(Arc::new(pool)),
+            settled: None,
+            drop_policy: DropPolicy::default(),
+        }
+    }
 
+    /// Handle to resize the pool while it runs
+    #[inline]
+    pub fn handle(&self) -> PoolHandle {
+        self.handle.clone()
+    }
+
+    /// Number of times a worker was restarted after it failed
+    #[inline]
+    pub fn restarts(&self) -> usize {
+        self.handle.restarts()
+    }
+
+    fn join_until(&mut self, deadline: Option<Instant>) -> ProcResult<()> {
+        if self.settled.is_some() {
+            return Err(ProcError::AlreadyJoined);
+        }
+        if let Some(dispatch) = &mut self.dispatch {
+            let output = join_until(dispatch, deadline);
+            if matches!(output, Err(ProcError::TimedOut)) {
+                return output;
+            }
+            self.dispatch = None;
+            if let Some(mut autoscaler) = self.autoscaler.take() {
+                autoscaler.forget();
+                if let Err(err) = autoscaler.join() {
+                    self.handle.0.fail(err);
+                }
+            }
+            self.handle.0.close();
+            if let Err(err) = output {
+                self.handle.0.fail(err);
+            }
+        }
+        let output = self.handle.0.join_workers(deadline);
+        if !matches!(output, Err(ProcError::TimedOut)) {
+            self.settled = Some(ProcStatus::Joined);
+        }
+        output
+    }
+}
+
+impl Proc for WorkerPool {
+    type Output = ();
+
+    fn join(&mut self) -> ProcResult<Self::Output> {
+        self.join_until(None)
+    }
+
+    fn join_deadline(&mut self, deadline: Instant) -> ProcResult<Self::Output> {
+        self.join_until(Some(deadline))
+    }
+
+    fn status(&self) -> ProcStatus {
+        if let Some(status) = self.settled {
+            return status;
+        }
+        let dispatched = self.dispatch.as_ref().is_none_or(Proc::is_finished);
+        let workers = self.handle.0.workers.lock().unwrap();
+        if dispatched && workers.threads.iter().all(Proc::is_finished) {
+            ProcStatus::Finished
+        } else {
+            ProcStatus::Running
+        }
+    }
+
+    fn forget(&mut self) {
+        if self.settled.is_some() {
+            return;
+        }
+        self.settled = Some(ProcStatus::Forgotten);
+        self.handle.0.close();
+        self.dispatch.iter_mut().for_each(Proc::forget);
+        self.autoscaler.iter_mut().for_each(Proc::forget);
+        let mut workers = self.handle.0.workers.lock().unwrap();
+        workers.threads.iter_mut().for_each(Proc::forget);
+    }
+
+    fn set_drop_policy(&mut self, policy: DropPolicy) {
+        self.drop_policy = policy;
+        for proc in self.dispatch.iter_mut().chain(&mut self.autoscaler) {
+            proc.set_drop_policy(policy);
+        }
+        let mut workers = self.handle.0.workers.lock().unwrap();
+        workers.drop_policy = policy;
+        for worker in &mut workers.threads {
+            worker.set_drop_policy(policy);
+        }
+    }
+}
+
+impl Drop for WorkerPool {
+    fn drop(&mut self) {
+        self.drop_policy.apply(self);
+    }
+}
+
+fn no_workers() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        "A pool requires at least one worker",
+    )
+}
+
+/// Spawns a worker for every id, and dispatches the items from `in_r` to them
+fn spawn_pool<I, O, W, F>(
+    threads: ThreadBuilder,
+    order: OutputOrder,
+    workers: usize,
+    channel_capacity: usize,
+    in_r: Receiver<I>,
+    out_s: Sender<O>,
+    mut worker: W,
+) -> impl Proc<Output = ()>
+where
+    I: Send + 'static,
+    O: Send + 'static,
+    W: FnMut(usize, Receiver<(I, Reply<O>)>) -> F,
+    F: FnOnce() -> anyhow::Result<()> + Send + 'static,
+{
+    if workers == 0 {
+        return blocking(|| Err(no_workers().into())).boxed();
+    }
+    let (dispatch, work_dispatch_r, _) = dispatch(order, workers, channel_capacity, in_r, out_s);
     (0..workers)
         .map(|worker_id| {
             let work_r = work_dispatch_r.clone();
-            thread(move || {
-                work_fn(worker_id, work_r);
-                Ok(())
-            })
+            let mut builder = threads.clone();
+            builder.name = builder.name.map(|prefix| format!("{prefix}-{worker_id}"));
+            match builder.spawn(worker(worker_id, work_r)) {
+                Ok(worker) => worker.boxed(),
+                Err(err) => blocking(move || Err(err.into())).boxed(),
+            }
         })
         .fold(dispatch.boxed(), |x, y| x.and_then(y).boxed())
 }
+
+/// Items dispatched to the workers of a pool, with the reply for their output
+type WorkQueue<I, O> = Receiver<(I, Reply<O>)>;
+
+/// Similar to [`dispatcher`], but drives the dispatcher on a background thread
+fn dispatch<I, O>(
+    order: OutputOrder,
+    workers: usize,
+    channel_capacity: usize,
+    in_r: Receiver<I>,
+    out_s: Sender<O>,
+) -> (impl Proc<Output = ()>, WorkQueue<I, O>, Arc<Semaphore>)
+where
+    I: Send + 'static,
+    O: Send + 'static,
+{
+    let (dispatcher, work_r, window) = dispatcher(order, workers, channel_capacity, in_r, out_s);
+    let dispatch = tokio(async move {
+        dispatcher.await;
+        Ok(())
+    });
+    (dispatch, work_r, window)
+}
+
+/// Dispatches the items from `in_r` to `workers`, through the returned channel,
+/// and delivers their outputs to `out_s`. The returned window holds a permit for
+/// every item which may still be dispatched
+fn dispatcher<I, O>(
+    order: OutputOrder,
+    workers: usize,
+    channel_capacity: usize,
+    in_r: Receiver<I>,
+    out_s: Sender<O>,
+) -> (
+    impl Future<Output = ()> + Send + 'static,
+    WorkQueue<I, O>,
+    Arc<Semaphore>,
+)
+where
+    I: Send + 'static,
+    O: Send + 'static,
+{
+    let (work_dispatch_s, work_dispatch_r) = bounded(channel_capacity);
+    // Bounded by the window, which holds a permit for every undelivered item
+    let (results_s, results_r) = unbounded();
+    let window = match order {
+        OutputOrder::Window(window) => window.max(1),
+        _ => channel_capacity + workers,
+    };
+    let window = Arc::new(Semaphore::new(window));
+    let (permits, released) = (window.clone(), window.clone());
+    // dispatch work to workers
+    let dispatch = async move {
+        let mut seq = 0;
+        while let Ok(msg) = in_r.recv_async().await {
+            // Closed once the outputs can no longer be delivered
+            match permits.acquire().await {
+                Ok(permit) => permit.forget(),
+                Err(_) => break,
+            }
+            let reply = Reply {
+                seq,
+                results: results_s.clone(),
+                sent: false,
+            };
+            if work_dispatch_s.send_async((msg, reply)).await.is_err() {
+                break;
+            }
+            seq += 1;
+        }
+    };
+    // collect output from workers
+    let collect = async move {
+        let _closed = ClosedOnDrop(released.clone());
+        // Outputs by sequence number, starting from the oldest undelivered item.
+        // Skipped items are kept as `Some(None)` until they are delivered
+        let mut pending = VecDeque::<Option<Option<O>>>::new();
+        let mut next = 0;
+        while let Ok((seq, output)) = results_r.recv_async().await {
+            match order {
+                OutputOrder::Unordered => pending.push_back(Some(output)),
+                _ => {
+                    let offset = seq - next;
+                    if pending.len() <= offset {
+                        pending.resize_with(offset + 1, || None);
+                    }
+                    pending[offset] = Some(output);
+                }
+            }
+            while pending.front().is_some_and(Option::is_some) {
+                let output = pending.pop_front().flatten().flatten();
+                next += 1;
+                released.add_permits(1);
+                if let Some(output) = output {
+                    if out_s.send_async(output).await.is_err() {
+                        return;
+                    }
+                }
+            }
+        }
+    };
+    let dispatch = async move {
+        futures::join!(dispatch, collect);
+    };
+    (dispatch, work_dispatch_r, window)
+}
+
+/// Closes the window, such that the dispatcher stops
+struct ClosedOnDrop(Arc<Semaphore>);
+
+impl Drop for ClosedOnDrop {
+    fn drop(&mut self) {
+        self.0.close();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn named_workers() {
+        let (in_s, in_r) = bounded(4);
+        let (out_s, out_r) = bounded(4);
+        let pool = with_worker_pool(2, 4, in_r, out_s, |_, work_r| {
+            while let Ok((_, reply)) = work_r.recv() {
+                let name = std::thread::current().name().map(ToString::to_string);
+                let _ = reply.send(name.unwrap_or_default());
+            }
+        });
+        for item in 0..4 {
+            in_s.send(item).unwrap();
+        }
+        drop(in_s);
+        drop(pool);
+        let names = out_r.drain().collect::<Vec<_>>();
+        assert_eq!(names.len(), 4);
+        assert!(names
+            .iter()
+            .all(|name| name == "pool-0" || name == "pool-1"));
+    }
+
+    type Gated = (usize, Option<Receiver<()>>);
+
+    /// Echoes every item, delaying those which carry a gate until it is released.
+    /// Reports every item once it was processed, before its output is delivered
+    fn gated_pool(
+        order: OutputOrder,
+    ) -> (Sender<Gated>, Receiver<usize>, Receiver<usize>, WorkerPool) {
+        let (in_s, in_r) = bounded(8);
+        let (out_s, out_r) = bounded(8);
+        let (processed_s, processed_r) = unbounded();
+        let pool = WorkerPoolBuilder::new(2)
+            .order(order)
+            .channel_capacity(8)
+            .spawn(in_r, out_s, move |_, (item, gate): Gated| {
+                if let Some(gate) = gate {
+                    let _ = gate.recv();
+                }
+                processed_s.send(item)?;
+                Ok(item)
+            });
+        (in_s, out_r, processed_r, pool)
+    }
+
+    fn send_gated(in_s: &Sender<Gated>, items: usize) -> Sender<()> {
+        let (gate_s, gate_r) = bounded(1);
+        in_s.send((0, Some(gate_r))).unwrap();
+        for item in 1..items {
+            in_s.send((item, None)).unwrap();
+        }
+        gate_s
+    }
+
+    #[test]
+    fn ordered() {
+        let (in_s, out_r, processed_r, mut pool) = gated_pool(OutputOrder::Ordered);
+        let gate = send_gated(&in_s, 6);
+        drop(in_s);
+        // All items but the gated one are done, yet none may overtake it
+        let mut processed = processed_r.iter().take(5).collect::<Vec<_>>();
+        processed.sort();
+        assert_eq!(processed, (1..6).collect::<Vec<_>>());
+        assert!(out_r.is_empty());
+        gate.send(()).unwrap();
+        pool.join().expect("could not join");
+        assert_eq!(
+            out_r.drain().collect::<Vec<_>>(),
+            (0..6).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn unordered() {
+        let (in_s, out_r, _processed_r, mut pool) = gated_pool(OutputOrder::Unordered);
+        let gate = send_gated(&in_s, 6);
+        drop(in_s);
+        let mut outputs = out_r.iter().take(5).collect::<Vec<_>>();
+        outputs.sort();
+        assert_eq!(outputs, (1..6).collect::<Vec<_>>());
+        gate.send(()).unwrap();
+        assert_eq!(out_r.recv(), Ok(0));
+        pool.join().expect("could not join");
+    }
+
+    #[test]
+    fn window() {
+        let (in_s, out_r, processed_r, mut pool) = gated_pool(OutputOrder::Window(3));
+        let gate = send_gated(&in_s, 6);
+        // Items 0..3 are in flight, item 3 is taken by the dispatcher but awaits room
+        // in the window, so items 4 & 5 are held back in the input
+        let mut processed = processed_r.iter().take(2).collect::<Vec<_>>();
+        processed.sort();
+        assert_eq!(processed, vec![1, 2]);
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while in_s.len() > 2 && Instant::now() < deadline {
+            std::thread::yield_now();
+        }
+        assert_eq!(in_s.len(), 2);
+        assert!(processed_r.is_empty());
+        assert!(out_r.is_empty());
+        drop(in_s);
+        gate.send(()).unwrap();
+        pool.join().expect("could not join");
+        assert_eq!(
+            out_r.drain().collect::<Vec<_>>(),
+            (0..6).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn skipped() {
+        let (in_s, in_r) = bounded(4);
+        let (out_s, out_r) = bounded(4);
+        let mut pool = with_worker_pool(2, 4, in_r, out_s, |_, work_r| {
+            while let Ok((item, reply)) = work_r.recv() {
+                if item % 2 == 0 {
+                    let _ = reply.send(item);
+                }
+            }
+        });
+        for item in 0..4 {
+            in_s.send(item).unwrap();
+        }
+        drop(in_s);
+        pool.join().expect("could not join");
+        assert_eq!(out_r.drain().collect::<Vec<_>>(), vec![0, 2]);
+    }
+
+    #[test]
+    fn worker_state() {
+        let (in_s, in_r) = bounded(8);
+        let (out_s, out_r) = bounded(8);
+        let (teardown_s, teardown_r) = unbounded();
+        let mut pool =
+            WorkerPoolBuilder::with_state(2, |worker_id| Ok((format!("worker-{worker_id}"), 0)))
+                .teardown(move |_, (_, processed)| teardown_s.send(processed).unwrap())
+                .spawn(
+                    in_r,
+                    out_s,
+                    |(name, processed): &mut (String, usize), item: usize| {
+                        *processed += 1;
+                        Ok(format!("{name}: {item}"))
+                    },
+                );
+        for item in 0..8 {
+            in_s.send(item).unwrap();
+        }
+        drop(in_s);
+        pool.join().expect("could not join");
+        let outputs = out_r.drain().collect::<Vec<_>>();
+        assert_eq!(outputs.len(), 8);
+        assert!(outputs.iter().all(|output| output.starts_with("worker-")));
+        assert_eq!(teardown_r.drain().sum::<usize>(), 8);
+    }
+
+    #[test]
+    fn failed_init() {
+        let (in_s, in_r) = bounded::<usize>(1);
+        let (out_s, _out_r) = bounded::<usize>(1);
+        let mut pool =
+            WorkerPoolBuilder::with_state(1, |_| Err::<(), _>(anyhow::anyhow!("no connection")))
+                .spawn(in_r, out_s, |_: &mut (), item| Ok(item));
+        drop(in_s);
+        let err = pool.join().unwrap_err();
+        assert_eq!(err.user_error().unwrap().to_string(), "no connection");
+    }
+
+    #[test]
+    fn failed_item() {
+        let (in_s, in_r) = bounded(4);
+        let (out_s, out_r) = bounded(4);
+        let (teardown_s, teardown_r) = unbounded();
+        let mut pool = WorkerPoolBuilder::new(1)
+            .teardown(move |worker_id, ()| teardown_s.send(worker_id).unwrap())
+            .spawn(in_r, out_s, |_, item: usize| {
+                anyhow::ensure!(item < 2, "item {item} failed");
+                Ok(item)
+            });
+        for item in 0..4 {
+            in_s.send(item).unwrap();
+        }
+        drop(in_s);
+        let err = pool.join().unwrap_err();
+        assert_eq!(err.user_error().unwrap().to_string(), "item 2 failed");
+        assert_eq!(out_r.drain().collect::<Vec<_>>(), vec![0, 1]);
+        assert_eq!(teardown_r.drain().collect::<Vec<_>>(), vec![0]);
+    }
+
+    #[test]
+    fn failed_item_panic() {
+        let (in_s, in_r) = bounded(4);
+        let (out_s, _out_r) = bounded::<usize>(4);
+        let (teardown_s, teardown_r) = unbounded();
+        let mut pool = WorkerPoolBuilder::new(1)
+            .teardown(move |worker_id, ()| teardown_s.send(worker_id).unwrap())
+            .spawn(in_r, out_s, |_, _: usize| panic!("boom"));
+        in_s.send(0).unwrap();
+        drop(in_s);
+        let err = pool.join().unwrap_err();
+        assert!(matches!(err, ProcError::Panicked(panic) if panic.message() == "boom"));
+        assert_eq!(teardown_r.drain().collect::<Vec<_>>(), vec![0]);
+    }
+
+    #[test]
+    fn supervised() {
+        let (in_s, in_r) = bounded(8);
+        let (out_s, out_r) = bounded(8);
+        let (dead_s, dead_r) = unbounded();
+        let (init_s, init_r) = unbounded();
+        let mut pool =
+            WorkerPoolBuilder::with_state(1, move |worker_id| Ok(init_s.send(worker_id)?))
+                .max_restarts(2)
+                .spawn_supervised(in_r, out_s, dead_s, |_, item: usize| match item {
+                    2 => panic!("boom"),
+                    4 => anyhow::bail!("item {item} failed"),
+                    item => Ok(item),
+                });
+        for item in 0..6 {
+            in_s.send(item).unwrap();
+        }
+        drop(in_s);
+        pool.join().expect("could not join");
+        assert_eq!(pool.restarts(), 2);
+        assert_eq!(init_r.drain().count(), 3);
+        assert_eq!(out_r.drain().collect::<Vec<_>>(), vec![0, 1, 3, 5]);
+        let dead = dead_r.drain().collect::<Vec<_>>();
+        assert_eq!(dead.len(), 2);
+        assert_eq!(*dead[0].item(), 2);
+        assert!(matches!(dead[0].error(), ProcError::Panicked(panic) if panic.message() == "boom"));
+        assert_eq!(*dead[1].item(), 4);
+        assert_eq!(dead[1].error().to_string(), "item 4 failed");
+    }
+
+    #[test]
+    fn restarts_exhausted() {
+        let (in_s, in_r) = bounded(4);
+        let (out_s, _out_r) = bounded(4);
+        let (dead_s, dead_r) = unbounded();
+        let mut pool = WorkerPoolBuilder::new(1).max_restarts(1).spawn_supervised(
+            in_r,
+            out_s,
+            dead_s,
+            |_, item: usize| -> anyhow::Result<usize> { anyhow::bail!("item {item} failed") },
+        );
+        for item in 0..4 {
+            in_s.send(item).unwrap();
+        }
+        drop(in_s);
+        let err = pool.join().unwrap_err();
+        assert_eq!(
+            err.user_error().unwrap().to_string(),
+            "worker 0 exhausted its restarts: item 1 failed"
+        );
+        assert_eq!(pool.restarts(), 1);
+        assert_eq!(
+            dead_r
+                .drain()
+                .map(DeadLetter::into_item)
+                .collect::<Vec<_>>(),
+            vec![0, 1]
+        );
+    }
+
+    #[test]
+    fn no_workers() {
+        let (_in_s, in_r) = bounded::<usize>(1);
+        let (out_s, _out_r) = bounded::<usize>(1);
+        let mut pool =
+            WorkerPoolBuilder::new(0).spawn(in_r.clone(), out_s.clone(), |_, item| Ok(item));
+        let err = pool.join().unwrap_err();
+        assert!(err
+            .user_error()
+            .unwrap()
+            .to_string()
+            .contains("at least one worker"));
+        let mut pool = with_worker_pool(0, 1, in_r.clone(), out_s.clone(), |_, _| {});
+        assert!(pool.join().is_err());
+        let runtime = TaskRuntime::new();
+        let mut pool =
+            AsyncWorkerPoolBuilder::new(0).spawn(&runtime, in_r, out_s, |item| async move { item });
+        assert!(pool.join().is_err());
+    }
+
+    #[test]
+    fn resize() {
+        let (in_s, in_r) = bounded(8);
+        let (out_s, out_r) = bounded(8);
+        let barrier = Arc::new(std::sync::Barrier::new(3));
+        let mut pool = WorkerPoolBuilder::new(1).spawn(in_r, out_s, move |_, item: usize| {
+            barrier.wait();
+            Ok(item)
+        });
+        let handle = pool.handle();
+        assert!(handle.resize(0).is_err());
+        // The items only complete once 3 workers run concurrently
+        handle.resize(3).unwrap();
+        assert_eq!(handle.workers(), 3);
+        for item in 0..3 {
+            in_s.send(item).unwrap();
+        }
+        drop(in_s);
+        pool.join().expect("could not join");
+        assert_eq!(out_r.drain().count(), 3);
+    }
+
+    #[test]
+    fn shrink() {
+        let (in_s, in_r) = bounded(8);
+        let (out_s, out_r) = bounded(8);
+        let (teardown_s, teardown_r) = unbounded();
+        let mut pool = WorkerPoolBuilder::new(3)
+            .teardown(move |worker_id, ()| teardown_s.send(worker_id).unwrap())
+            .spawn(in_r, out_s, |_, _: usize| Ok(std::thread::current().id()));
+        let handle = pool.handle();
+        handle.resize(1).unwrap();
+        assert_eq!(handle.workers(), 1);
+        assert_eq!(teardown_r.iter().take(2).count(), 2);
+        for item in 0..8 {
+            in_s.send(item).unwrap();
+        }
+        drop(in_s);
+        pool.join().expect("could not join");
+        let outputs = out_r.drain().collect::<Vec<_>>();
+        assert_eq!(outputs.len(), 8);
+        assert!(outputs.iter().all(|thread| *thread == outputs[0]));
+    }
+
+    #[test]
+    fn queued() {
+        let (in_s, in_r) = bounded(8);
+        let (out_s, out_r) = bounded(8);
+        let (gate_s, gate_r) = bounded::<()>(0);
+        let mut pool = WorkerPoolBuilder::new(1).channel_capacity(1).spawn(
+            in_r,
+            out_s,
+            move |_, item: usize| {
+                let _ = gate_r.recv();
+                Ok(item)
+            },
+        );
+        let handle = pool.handle();
+        for item in 0..6 {
+            in_s.send(item).unwrap();
+        }
+        // Item 0 is processed, item 1 is in the dispatch channel & item 2 awaits room
+        // in the window, leaving items 3..6 in the input
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while in_s.len() > 3 && Instant::now() < deadline {
+            std::thread::yield_now();
+        }
+        assert_eq!(handle.queued(), 4);
+        drop((in_s, gate_s));
+        pool.join().expect("could not join");
+        assert_eq!(out_r.drain().count(), 6);
+    }
+
+    #[test]
+    fn autoscale() {
+        let (in_s, in_r) = bounded(16);
+        let (out_s, out_r) = bounded(16);
+        let mut pool = WorkerPoolBuilder::new(1)
+            .channel_capacity(16)
+            .autoscale(Autoscaler::new(1, 4).interval(Duration::from_millis(10)))
+            .spawn(in_r, out_s, |_, item: usize| {
+                std::thread::sleep(Duration::from_millis(20));
+                Ok(item)
+            });
+        let handle = pool.handle();
+        for item in 0..16 {
+            in_s.send(item).unwrap();
+        }
+        let mut peak = 0;
+        for _ in 0..16 {
+            out_r.recv().unwrap();
+            peak = peak.max(handle.workers());
+        }
+        assert!(peak > 1);
+        // Scales back down once idle
+        let idle = Instant::now() + Duration::from_secs(5);
+        while handle.workers() > 1 && Instant::now() < idle {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(handle.workers(), 1);
+        drop(in_s);
+        pool.join().expect("could not join");
+    }
+
+    #[test]
+    fn async_workers() {
+        let runtime = TaskRuntime::new();
+        let (in_s, in_r) = bounded(8);
+        let (out_s, out_r) = bounded(8);
+        let running = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+        let (running_fn, peak_fn) = (running.clone(), peak.clone());
+        let mut pool = AsyncWorkerPoolBuilder::new(2).channel_capacity(8).spawn(
+            &runtime,
+            in_r,
+            out_s,
+            move |item: u64| {
+                let (running, peak) = (running_fn.clone(), peak_fn.clone());
+                async move {
+                    peak.fetch_max(running.fetch_add(1, Ordering::SeqCst) + 1, Ordering::SeqCst);
+                    // Later items complete first
+                    ::tokio::time::sleep(Duration::from_millis(20 - 2 * item)).await;
+                    running.fetch_sub(1, Ordering::SeqCst);
+                    item
+                }
+            },
+        );
+        for item in 0..8 {
+            in_s.send(item).unwrap();
+        }
+        drop(in_s);
+        pool.join().expect("could not join");
+        assert_eq!(
+            out_r.drain().collect::<Vec<_>>(),
+            (0..8).collect::<Vec<_>>()
+        );
+        assert_eq!(peak.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn async_worker_panic() {
+        let runtime = TaskRuntime::new();
+        let (in_s, in_r) = bounded(4);
+        let (out_s, out_r) = bounded(4);
+        let mut pool =
+            AsyncWorkerPoolBuilder::new(1).spawn(&runtime, in_r, out_s, |item: usize| async move {
+                assert!(item != 1, "boom");
+                item
+            });
+        for item in 0..4 {
+            in_s.send(item).unwrap();
+        }
+        drop(in_s);
+        pool.join().expect("could not join");
+        assert_eq!(out_r.drain().collect::<Vec<_>>(), vec![0, 2, 3]);
+    }
+}
@@ -0,0 +1,317 @@
+use crate::drop_policy::DropPolicy;
+use crate::error::{ProcError, ProcResult};
+use crate::proc::{Proc, ProcStatus};
+use flume::{Receiver, RecvTimeoutError};
+use std::fmt;
+use std::io::{self, Read};
+use std::process::{Child, Command, Output, Stdio};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+const DEFAULT_GRACE_PERIOD: Duration = Duration::from_secs(5);
+
+/// Error returned when a child process exits unsuccessfully.
+/// Carries the exit status and everything the process wrote to stdout & stderr
+pub struct CommandError {
+    output: Output,
+}
+
+impl CommandError {
+    #[inline]
+    pub fn output(&self) -> &Output {
+        &self.output
+    }
+
+    #[inline]
+    pub fn into_output(self) -> Output {
+        self.output
+    }
+}
+
+impl fmt::Debug for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CommandError")
+            .field("status", &self.output.status)
+            .field("stderr", &String::from_utf8_lossy(&self.output.stderr))
+            .finish_non_exhaustive()
+    }
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Process exited unsuccessfully ({})", self.output.status)
+    }
+}
+
+impl std::error::Error for CommandError {}
+
+/// Instance of a [`Proc`] which runs a child OS process, capturing its stdout & stderr.
+/// Output is read until the pipes close, which may outlive the process itself
+/// if it handed them down to its own children
+pub struct ChildProcess {
+    child: Option<Arc<Mutex<Child>>>,
+    output: Receiver<io::Result<Output>>,
+    exited: Receiver<()>,
+    grace_period: Duration,
+    joined: bool,
+    forgotten: bool,
+    drop_policy: DropPolicy,
+}
+
+impl ChildProcess {
+    pub(crate) fn spawn(mut command: Command) -> Self {
+        command.stdout(Stdio::piped()).stderr(Stdio::piped());
+        let (output_tx, output) = flume::bounded(1);
+        let (exited_tx, exited) = flume::bounded::<()>(0);
+        let child = match command.spawn() {
+            Ok(child) => {
+                let child = Arc::new(Mutex::new(child));
+                let waiter = child.clone();
+                std::thread::Builder::new()
+                    .name("command".into())
+                    .spawn(move || {
+                        let output = wait_with_output(&waiter);
+                        drop(exited_tx);
+                        let _ = output_tx.send(output);
+                    })
+                    .expect("failed to spawn thread");
+                Some(child)
+            }
+            Err(err) => {
+                let _ = output_tx.send(Err(err));
+                None
+            }
+        };
+        Self {
+            child,
+            output,
+            exited,
+            grace_period: DEFAULT_GRACE_PERIOD,
+            joined: false,
+            forgotten: false,
+            drop_policy: DropPolicy::default(),
+        }
+    }
+
+    /// Time the process is given to exit after being asked to terminate, before it is killed
+    pub fn grace_period(mut self, grace_period: Duration) -> Self {
+        self.grace_period = grace_period;
+        self
+    }
+
+    /// OS-assigned identifier of the child process, if it was spawned
+    pub fn id(&self) -> Option<u32> {
+        self.child.as_ref().map(|child| child.lock().unwrap().id())
+    }
+
+    fn finish(&mut self, output: Option<io::Result<Output>>) -> ProcResult<Output> {
+        self.joined = true;
+        match output {
+            None => Err(ProcError::RuntimeShutdown),
+            Some(Err(err)) => Err(anyhow::Error::from(err).into()),
+            Some(Ok(output)) if output.status.success() => Ok(output),
+            Some(Ok(_)) if self.forgotten => Err(ProcError::Cancelled),
+            Some(Ok(output)) => Err(anyhow::Error::from(CommandError { output }).into()),
+        }
+    }
+}
+
+impl Proc for ChildProcess {
+    type Output = Output;
+
+    /// Waits for the process to exit. Fails with a [`CommandError`] on a non-zero exit status
+    fn join(&mut self) -> ProcResult<Self::Output> {
+        if self.joined {
+            return Err(ProcError::AlreadyJoined);
+        }
+        let output = self.output.recv().ok();
+        self.finish(output)
+    }
+
+    fn join_deadline(&mut self, deadline: Instant) -> ProcResult<Self::Output> {
+        if self.joined {
+            return Err(ProcError::AlreadyJoined);
+        }
+        match self.output.recv_deadline(deadline) {
+            Ok(output) => self.finish(Some(output)),
+            Err(RecvTimeoutError::Timeout) => Err(ProcError::TimedOut),
+            Err(RecvTimeoutError::Disconnected) => self.finish(None),
+        }
+    }
+
+    fn status(&self) -> ProcStatus {
+        if self.joined {
+            ProcStatus::Joined
+        } else if self.forgotten {
+            ProcStatus::Forgotten
+        } else if !self.output.is_empty() {
+            ProcStatus::Finished
+        } else {
+            ProcStatus::Running
+        }
+    }
+
+    /// Asks the process to terminate, killing it if it is still running after the grace period
+    fn forget(&mut self) {
+        if self.joined || std::mem::replace(&mut self.forgotten, true) {
+            return;
+        }
+        if let Some(child) = &self.child {
+            terminate(child, self.exited.clone(), self.grace_period);
+        }
+    }
+
+    fn set_drop_policy(&mut self, policy: DropPolicy) {
+        self.drop_policy = policy;
+    }
+}
+
+impl Drop for ChildProcess {
+    fn drop(&mut self) {
+        self.drop_policy.apply(self);
+    }
+}
+
+/// Drains stdout & stderr, then reaps the process
+fn wait_with_output(child: &Mutex<Child>) -> io::Result<Output> {
+    let (stdout, stderr) = {
+        let mut child = child.lock().unwrap();
+        (child.stdout.take(), child.stderr.take())
+    };
+    let stderr = std::thread::spawn(move || read_to_end(stderr));
+    let stdout = read_to_end(stdout);
+    let stderr = stderr.join().unwrap_or_else(|_| Ok(Vec::new()));
+    Ok(Output {
+        status: wait(child)?,
+        stdout: stdout?,
+        stderr: stderr?,
+    })
+}
+
+fn read_to_end(pipe: Option<impl Read>) -> io::Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    if let Some(mut pipe) = pipe {
+        pipe.read_to_end(&mut buf)?;
+    }
+    Ok(buf)
+}
+
+/// Waits for the process to exit without reaping it, so its pid cannot be reused
+/// while [`signal`] may still target it. Only reaps while holding the lock
+#[cfg(unix)]
+fn wait(child: &Mutex<Child>) -> io::Result<std::process::ExitStatus> {
+    let pid = child.lock().unwrap().id() as libc::id_t;
+    loop {
+        let mut info = unsafe { std::mem::zeroed::<libc::siginfo_t>() };
+        let res =
+            unsafe { libc::waitid(libc::P_PID, pid, &mut info, libc::WEXITED | libc::WNOWAIT) };
+        if res == 0 || io::Error::last_os_error().kind() != io::ErrorKind::Interrupted {
+            break;
+        }
+    }
+    child.lock().unwrap().wait()
+}
+
+#[cfg(not(unix))]
+fn wait(child: &Mutex<Child>) -> io::Result<std::process::ExitStatus> {
+    loop {
+        if let Some(status) = child.lock().unwrap().try_wait()? {
+            return Ok(status);
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+}
+
+#[cfg(unix)]
+fn terminate(child: &Arc<Mutex<Child>>, exited: Receiver<()>, grace_period: Duration) {
+    if signal(child, libc::SIGTERM) {
+        let child = child.clone();
+        std::thread::spawn(move || {
+            if let Err(RecvTimeoutError::Timeout) = exited.recv_timeout(grace_period) {
+                signal(&child, libc::SIGKILL);
+            }
+        });
+    }
+}
+
+#[cfg(not(unix))]
+fn terminate(child: &Arc<Mutex<Child>>, _exited: Receiver<()>, _grace_period: Duration) {
+    let _ = child.lock().unwrap().kill();
+}
+
+/// Sends a signal to the process if it has not exited yet. Returns whether it was delivered
+#[cfg(unix)]
+fn signal(child: &Mutex<Child>, signal: libc::c_int) -> bool {
+    let mut child = child.lock().unwrap();
+    if !matches!(child.try_wait(), Ok(None)) {
+        return false;
+    }
+    unsafe { libc::kill(child.id() as libc::pid_t, signal) == 0 }
+}
+
+#[cfg(all(test, unix))]
+mod test {
+    use super::*;
+    use crate::proc_ext::ProcExt;
+    use crate::{blocking, command};
+
+    fn sh(script: &str) -> ChildProcess {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c").arg(script);
+        command(cmd)
+    }
+
+    #[test]
+    fn captures_output() {
+        let output = sh("echo out; echo err >&2").join().expect("could not join");
+        assert_eq!(output.stdout, b"out\n");
+        assert_eq!(output.stderr, b"err\n");
+    }
+
+    #[test]
+    fn failed_exit() {
+        let err = sh("echo err >&2; exit 3").join().unwrap_err();
+        let err = err
+            .user_error()
+            .and_then(|err| err.downcast_ref::<CommandError>())
+            .expect("expected a command error");
+        assert_eq!(err.output().status.code(), Some(3));
+        assert_eq!(err.output().stderr, b"err\n");
+    }
+
+    #[test]
+    fn spawn_failure() {
+        let err = command(Command::new("/nonexistent")).join().unwrap_err();
+        assert!(err.user_error().is_some());
+    }
+
+    #[test]
+    fn forget_terminates() {
+        let mut proc = sh("exec sleep 30");
+        proc.forget();
+        assert!(proc
+            .join_timeout(Duration::from_secs(5))
+            .unwrap_err()
+            .is_cancelled());
+    }
+
+    #[test]
+    fn forget_kills_after_grace_period() {
+        let mut proc = sh("trap '' TERM; echo ready; while :; do :; done")
+            .grace_period(Duration::from_millis(50));
+        std::thread::sleep(Duration::from_millis(50));
+        proc.forget();
+        assert!(proc
+            .join_timeout(Duration::from_secs(5))
+            .unwrap_err()
+            .is_cancelled());
+    }
+
+    #[test]
+    fn composes() {
+        let mut proc = sh("exit 1")
+            .or_else(sh("echo fallback"))
+            .and_then(blocking(|| Ok(())));
+        assert!(proc.join().is_ok());
+    }
+}
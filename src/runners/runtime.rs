@@ -1,17 +1,270 @@
-use flume::Sender;
-use std::thread;
-use tokio::runtime::Handle;
-
-/// [`TaskRuntime`] is a runtime for a set of tasks that is either dedicated for a set of tasks
-/// or derived from the currently active runtime
-pub enum TaskRuntime {
-    Owned {
-        handle: Handle,
-        shutdown: Sender<()>,
-    },
-    Entered(Handle),
+use crate::error::{ProcError, ProcResult};
+use crate::panic::catch_unwind_future;
+use crate::runners::spawner::{Spawner, TaskHandle};
+use flume::{Receiver, RecvTimeoutError, Sender};
+use futures::future::{AbortHandle, Abortable};
+use std::cell::Cell;
+use std::future::Future;
+use std::io;
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "smol")]
+use crate::runners::spawner::SmolSpawner;
+#[cfg(feature = "tokio")]
+use crate::runners::spawner::TokioSpawner;
+
+/// [`TaskRuntime`] is a runtime for a set of tasks that is either dedicated for a set of tasks,
+/// shared through [`TaskRuntime::global`], or derived from an existing executor through a [`Spawner`]
+pub struct TaskRuntime {
+    spawner: Arc<dyn Spawner>,
+    outstanding: Arc<Outstanding>,
+    owned: Option<Owned>,
+}
+
+thread_local! {
+    // Dedicated runtime which the current thread belongs to, if any
+    static RUNTIME_ID: Cell<usize> = const { Cell::new(0) };
+}
+
+static NEXT_RUNTIME_ID: AtomicUsize = AtomicUsize::new(1);
+
+/// Background threads driving a dedicated runtime
+struct Owned {
+    // Identifies the threads of the runtime, see `RUNTIME_ID`
+    id: usize,
+    // Dropped to signal shutdown to all runtime threads
+    shutdown: Option<Sender<()>>,
+    // Disconnects once all runtime threads exited
+    exited: Receiver<()>,
+    threads: Vec<JoinHandle<()>>,
+}
+
+/// Tasks spawned through a [`TaskRuntime`] which did not complete yet
+#[derive(Default)]
+struct Outstanding {
+    count: Mutex<usize>,
+    idle: Condvar,
+}
+
+struct OutstandingGuard(Arc<Outstanding>);
+
+impl Outstanding {
+    fn enter(self: &Arc<Self>) -> OutstandingGuard {
+        *self.count.lock().unwrap() += 1;
+        OutstandingGuard(self.clone())
+    }
+
+    /// Waits for all tasks to complete. Returns whether they did before the deadline
+    fn wait_idle(&self, deadline: Option<Instant>) -> bool {
+        let mut count = self.count.lock().unwrap();
+        while *count > 0 {
+            count = match deadline {
+                Some(deadline) => {
+                    let remaining = deadline.saturating_duration_since(Instant::now());
+                    if remaining.is_zero() {
+                        return false;
+                    }
+                    self.idle.wait_timeout(count, remaining).unwrap().0
+                }
+                None => self.idle.wait(count).unwrap(),
+            };
+        }
+        true
+    }
+}
+
+impl Drop for OutstandingGuard {
+    fn drop(&mut self) {
+        let mut count = self.0.count.lock().unwrap();
+        *count -= 1;
+        if *count == 0 {
+            self.0.idle.notify_all();
+        }
+    }
+}
+
+/// Scheduler of a dedicated [`TaskRuntime`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Flavor {
+    /// Drives all tasks on a single background thread
+    #[default]
+    CurrentThread,
+    /// Distributes tasks over a pool of worker threads
+    MultiThread,
+}
+
+/// Configures a dedicated [`TaskRuntime`], backed by tokio if enabled and smol otherwise
+#[derive(Debug, Clone, Default)]
+pub struct TaskRuntimeBuilder {
+    flavor: Flavor,
+    worker_threads: Option<usize>,
+    thread_name: Option<String>,
+    enable_io: bool,
+    enable_time: bool,
+    #[cfg(all(feature = "tokio", feature = "smol"))]
+    smol: bool,
+}
+
+impl TaskRuntimeBuilder {
+    /// Scheduler of the runtime, which defaults to [`Flavor::CurrentThread`]
+    #[inline]
+    pub fn flavor(mut self, flavor: Flavor) -> Self {
+        self.flavor = flavor;
+        self
+    }
+
+    /// Number of worker threads of a [`Flavor::MultiThread`] runtime,
+    /// which defaults to the available parallelism
+    #[inline]
+    pub fn worker_threads(mut self, workers: usize) -> Self {
+        self.worker_threads = Some(workers);
+        self
+    }
+
+    /// Name of the runtime threads, which defaults to `circuits-runtime`
+    #[inline]
+    pub fn thread_name(mut self, name: impl Into<String>) -> Self {
+        self.thread_name = Some(name.into());
+        self
+    }
+
+    /// Enables the tokio I/O driver. The smol reactor is always enabled
+    #[inline]
+    pub fn enable_io(mut self) -> Self {
+        self.enable_io = true;
+        self
+    }
+
+    /// Enables the tokio time driver. The smol reactor is always enabled
+    #[inline]
+    pub fn enable_time(mut self) -> Self {
+        self.enable_time = true;
+        self
+    }
+
+    /// Enables both the tokio I/O & time drivers
+    #[inline]
+    pub fn enable_all(self) -> Self {
+        self.enable_io().enable_time()
+    }
+
+    /// Backs the runtime by smol, even though tokio is enabled
+    #[cfg(all(feature = "tokio", feature = "smol"))]
+    #[inline]
+    pub fn smol(mut self) -> Self {
+        self.smol = true;
+        self
+    }
+
+    /// Starts the runtime on background threads
+    pub fn build(self) -> io::Result<TaskRuntime> {
+        if self.worker_threads == Some(0) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "A runtime requires at least one worker thread",
+            ));
+        }
+        #[cfg(all(feature = "tokio", feature = "smol"))]
+        if self.smol {
+            return self.build_smol();
+        }
+        #[cfg(feature = "tokio")]
+        return self.build_tokio();
+        #[cfg(not(feature = "tokio"))]
+        return self.build_smol();
+    }
+
+    fn workers(&self) -> usize {
+        match self.flavor {
+            Flavor::CurrentThread => 1,
+            Flavor::MultiThread => self
+                .worker_threads
+                .unwrap_or_else(|| thread::available_parallelism().map_or(1, NonZeroUsize::get)),
+        }
+    }
+
+    fn name(&self) -> &str {
+        self.thread_name.as_deref().unwrap_or("circuits-runtime")
+    }
+
+    #[cfg(feature = "tokio")]
+    fn build_tokio(self) -> io::Result<TaskRuntime> {
+        let mut builder = match self.flavor {
+            Flavor::CurrentThread => tokio::runtime::Builder::new_current_thread(),
+            Flavor::MultiThread => {
+                let mut builder = tokio::runtime::Builder::new_multi_thread();
+                builder.worker_threads(self.workers());
+                builder
+            }
+        };
+        let id = NEXT_RUNTIME_ID.fetch_add(1, Ordering::Relaxed);
+        builder.thread_name(self.name());
+        // Marks both the worker & blocking threads
+        builder.on_thread_start(move || RUNTIME_ID.set(id));
+        if self.enable_io {
+            builder.enable_io();
+        }
+        if self.enable_time {
+            builder.enable_time();
+        }
+        let runtime = builder.build()?;
+        let spawner = TokioSpawner::new(runtime.handle().clone());
+        let (shutdown, shutdown_rx) = flume::bounded::<()>(1);
+        let (exited_tx, exited) = flume::bounded::<()>(0);
+        let thread = thread::Builder::new()
+            .name(self.name().into())
+            .spawn(move || {
+                RUNTIME_ID.set(id);
+                // Drives a current-thread runtime until the shutdown signal, then drops its tasks
+                let _ = runtime.block_on(shutdown_rx.recv_async());
+                drop(runtime);
+                drop(exited_tx);
+            })?;
+        Ok(TaskRuntime::owned(
+            id,
+            spawner,
+            shutdown,
+            exited,
+            vec![thread],
+        ))
+    }
+
+    #[cfg(feature = "smol")]
+    fn build_smol(self) -> io::Result<TaskRuntime> {
+        let executor = Arc::new(smol::Executor::new());
+        let spawner = SmolSpawner::dedicated(&executor);
+        let (shutdown, shutdown_rx) = flume::bounded::<()>(1);
+        let (exited_tx, exited) = flume::bounded::<()>(0);
+        let id = NEXT_RUNTIME_ID.fetch_add(1, Ordering::Relaxed);
+        let threads = (0..self.workers())
+            .map(|index| {
+                let executor = executor.clone();
+                let shutdown_rx = shutdown_rx.clone();
+                let exited_tx = exited_tx.clone();
+                let name = match self.flavor {
+                    Flavor::CurrentThread => self.name().to_string(),
+                    Flavor::MultiThread => format!("{}-{index}", self.name()),
+                };
+                thread::Builder::new().name(name).spawn(move || {
+                    RUNTIME_ID.set(id);
+                    // Dropping the executor on shutdown also drops its tasks
+                    let _ = smol::block_on(executor.run(shutdown_rx.recv_async()));
+                    drop(executor);
+                    drop(exited_tx);
+                })
+            })
+            .collect::<io::Result<_>>()?;
+        Ok(TaskRuntime::owned(id, spawner, shutdown, exited, threads))
+    }
 }
 
+static GLOBAL: Mutex<Option<TaskRuntime>> = Mutex::new(None);
+static GLOBAL_WORKERS: AtomicUsize = AtomicUsize::new(0);
+
 impl Default for TaskRuntime {
     fn default() -> Self {
         Self::new()
@@ -19,48 +272,291 @@ impl Default for TaskRuntime {
 }
 
 impl TaskRuntime {
+    /// Configures a dedicated runtime, see [`TaskRuntimeBuilder`]
+    #[inline]
+    pub fn builder() -> TaskRuntimeBuilder {
+        TaskRuntimeBuilder::default()
+    }
+
+    /// Creates a dedicated runtime, backed by tokio if enabled and smol otherwise
     pub fn new() -> Self {
-        let (runtime_tx, runtime_rx) = flume::bounded(1);
-        thread::spawn(move || {
-            // Create a shutdown signal
-            let (shutdown_tx, shutdown_rx) = flume::bounded(1);
+        Self::builder()
+            .enable_all()
+            .build()
+            .expect("failed to start runtime")
+    }
 
-            // Create a new single-threaded runtime
-            let runtime = tokio::runtime::Builder::new_current_thread()
-                .enable_all()
-                .build()
-                .unwrap();
+    /// Creates a dedicated tokio current-thread runtime on a background thread
+    #[cfg(feature = "tokio")]
+    pub fn tokio() -> Self {
+        Self::new()
+    }
 
-            // Share the handle
-            runtime_tx
-                .send(Self::Owned {
-                    handle: runtime.handle().clone(),
-                    shutdown: shutdown_tx,
-                })
-                .unwrap();
+    /// Creates a dedicated smol executor on a background thread
+    #[cfg(feature = "smol")]
+    pub fn smol() -> Self {
+        let builder = Self::builder();
+        #[cfg(feature = "tokio")]
+        let builder = builder.smol();
+        builder.build().expect("failed to start runtime")
+    }
 
-            // Allow tokio::spawn within the new context
-            let _ = runtime.enter();
+    fn owned(
+        id: usize,
+        spawner: impl Spawner + 'static,
+        shutdown: Sender<()>,
+        exited: Receiver<()>,
+        threads: Vec<JoinHandle<()>>,
+    ) -> Self {
+        Self {
+            spawner: Arc::new(spawner),
+            outstanding: Default::default(),
+            owned: Some(Owned {
+                id,
+                shutdown: Some(shutdown),
+                exited,
+                threads,
+            }),
+        }
+    }
 
-            // Wait for the shutdown signal
-            let _ = runtime.block_on(shutdown_rx.recv_async());
+    /// Spawns tasks onto an existing executor, which is not shut down with this runtime
+    pub fn with_spawner(spawner: impl Spawner + 'static) -> Self {
+        Self {
+            spawner: Arc::new(spawner),
+            outstanding: Default::default(),
+            owned: None,
+        }
+    }
+
+    /// Spawns tasks onto the global runtime, which is started on first use.
+    /// It is multi-threaded and only stops on [`TaskRuntime::shutdown_global`]
+    pub fn global() -> Self {
+        let mut global = GLOBAL.lock().unwrap();
+        let global = global.get_or_insert_with(|| {
+            let builder = Self::builder().flavor(Flavor::MultiThread).enable_all();
+            let builder = match GLOBAL_WORKERS.load(Ordering::Relaxed) {
+                0 => builder,
+                workers => builder.worker_threads(workers),
+            };
+            builder.build().expect("failed to start the global runtime")
         });
-        runtime_rx.recv().unwrap()
+        // Each handle tracks its own tasks, so shutting one down does not wait on others
+        Self {
+            spawner: global.spawner.clone(),
+            outstanding: Default::default(),
+            owned: None,
+        }
+    }
+
+    /// Sets the number of worker threads of the global runtime, which defaults to
+    /// the available parallelism. Only applies when it is (re)started
+    pub fn set_global_workers(workers: usize) {
+        GLOBAL_WORKERS.store(workers, Ordering::Relaxed);
+    }
+
+    /// Shuts down the global runtime & joins its threads. Pending tasks are cancelled.
+    /// A new global runtime is started on the next use. Fails with [`ProcError::WouldDeadlock`]
+    /// when called from one of its own threads, which would have to join itself
+    pub fn shutdown_global() -> ProcResult<()> {
+        let global = {
+            let mut global = GLOBAL.lock().unwrap();
+            if global.as_ref().is_some_and(TaskRuntime::is_current) {
+                return Err(ProcError::WouldDeadlock);
+            }
+            global.take()
+        };
+        if let Some(mut global) = global {
+            global.shutdown();
+            global.join_threads();
+        }
+        Ok(())
     }
 
-    pub fn handle(&self) -> &Handle {
-        match self {
-            TaskRuntime::Owned { handle, .. } => handle,
-            TaskRuntime::Entered(handle) => handle,
+    /// Derives a runtime from the current multi-threaded runtime, or uses the global one.
+    /// Tasks spawned onto a current-thread runtime could not progress while it is blocked on a join
+    pub(crate) fn current_or_global() -> Self {
+        #[cfg(feature = "tokio")]
+        if let Ok(handle) = tokio::runtime::Handle::try_current() {
+            if handle.runtime_flavor() != tokio::runtime::RuntimeFlavor::CurrentThread {
+                return Self::with_spawner(TokioSpawner::new(handle));
+            }
         }
+        Self::global()
+    }
+
+    /// Spawns onto the same runtime, without keeping a dedicated runtime alive
+    #[cfg(feature = "tokio")]
+    pub(crate) fn share(&self) -> Self {
+        Self {
+            spawner: self.spawner.clone(),
+            outstanding: self.outstanding.clone(),
+            owned: None,
+        }
+    }
+
+    /// Moves the ownership of a dedicated runtime into a new [`TaskRuntime`],
+    /// leaving this one to only spawn onto it
+    pub(crate) fn take_owned(&mut self) -> Self {
+        Self {
+            spawner: self.spawner.clone(),
+            outstanding: self.outstanding.clone(),
+            owned: self.owned.take(),
+        }
+    }
+
+    #[inline]
+    pub fn spawner(&self) -> &dyn Spawner {
+        self.spawner.as_ref()
+    }
+
+    /// Spawns a future onto the runtime, capturing its output or panic
+    pub fn spawn<F>(&self, fut: F) -> TaskHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        let (output_tx, output) = flume::bounded(1);
+        let (abort, registration) = AbortHandle::new_pair();
+        let task = Abortable::new(
+            async move {
+                let _ = output_tx.send(catch_unwind_future(fut).await);
+            },
+            registration,
+        );
+        // Released once the task completes, or is dropped by its executor
+        let outstanding = self.outstanding.enter();
+        self.spawner.spawn_detached(Box::pin(async move {
+            let _ = task.await;
+            drop(outstanding);
+        }));
+        TaskHandle::new(output, abort)
+    }
+
+    /// Runs a blocking wait on tasks spawned onto this runtime, see [`Spawner::block_in_place`]
+    pub(crate) fn block_in_place<R>(&self, f: impl FnOnce() -> R) -> ProcResult<R> {
+        let mut f = Some(f);
+        let mut output = None;
+        self.spawner
+            .block_in_place(&mut || output = f.take().map(|f| f()))?;
+        output.ok_or(ProcError::WouldDeadlock)
     }
 
+    /// Signals a dedicated runtime to shut down without waiting, cancelling its pending tasks
     pub fn shutdown(&mut self) {
-        match self {
-            TaskRuntime::Owned { shutdown, .. } => {
-                let _ = shutdown.send(());
+        if let Some(owned) = &mut self.owned {
+            owned.shutdown = None;
+        }
+    }
+
+    /// Waits for the tasks spawned through this runtime to complete, then shuts down a dedicated
+    /// runtime & joins its threads. Gives up with [`ProcError::TimedOut`] once the timeout elapsed,
+    /// cancelling the pending tasks
+    pub fn shutdown_timeout(&mut self, timeout: Duration) -> ProcResult<()> {
+        let deadline = Instant::now().checked_add(timeout);
+        let idle = self.outstanding.wait_idle(deadline);
+        self.shutdown();
+        if let (Some(owned), Some(deadline)) = (&self.owned, deadline) {
+            // Threads which did not exit in time are left to exit in the background
+            if let Err(RecvTimeoutError::Timeout) = owned.exited.recv_deadline(deadline) {
+                return Err(ProcError::TimedOut);
             }
-            TaskRuntime::Entered(_) => {}
+        }
+        self.join_threads();
+        match idle {
+            true => Ok(()),
+            false => Err(ProcError::TimedOut),
+        }
+    }
+
+    /// Whether the current thread belongs to this dedicated runtime
+    fn is_current(&self) -> bool {
+        self.owned
+            .as_ref()
+            .is_some_and(|owned| RUNTIME_ID.get() == owned.id)
+    }
+
+    fn join_threads(&mut self) {
+        if let Some(owned) = self.owned.take() {
+            for thread in owned.threads {
+                let _ = thread.join();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::error::ProcError;
+    use crate::runtime::{Flavor, TaskRuntime, TaskRuntimeBuilder};
+    use std::time::Duration;
+
+    fn builders() -> Vec<TaskRuntimeBuilder> {
+        vec![
+            TaskRuntime::builder(),
+            #[cfg(all(feature = "tokio", feature = "smol"))]
+            TaskRuntime::builder().smol(),
+        ]
+    }
+
+    #[test]
+    fn builder() {
+        for builder in builders() {
+            let runtime = builder
+                .flavor(Flavor::MultiThread)
+                .worker_threads(2)
+                .thread_name("custom")
+                .enable_all()
+                .build()
+                .expect("could not build");
+            let name = futures::executor::block_on(
+                runtime.spawn(async { std::thread::current().name().map(String::from) }),
+            );
+            assert!(name.unwrap().unwrap().starts_with("custom"));
+        }
+    }
+
+    #[test]
+    fn no_workers() {
+        let err = TaskRuntime::builder()
+            .flavor(Flavor::MultiThread)
+            .worker_threads(0)
+            .build()
+            .err()
+            .expect("expected a build error");
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn shutdown_timeout_waits() {
+        for builder in builders() {
+            let mut runtime = builder.build().expect("could not build");
+            let (tx, rx) = flume::bounded(1);
+            let task = runtime.spawn(async move { rx.recv_async().await.unwrap() });
+            std::thread::spawn(move || {
+                std::thread::sleep(Duration::from_millis(50));
+                tx.send(1).unwrap();
+            });
+            runtime
+                .shutdown_timeout(Duration::from_secs(5))
+                .expect("could not shut down");
+            assert!(task.is_finished());
+            assert_eq!(futures::executor::block_on(task).unwrap(), 1);
+        }
+    }
+
+    #[test]
+    fn shutdown_timeout_expires() {
+        for builder in builders() {
+            let mut runtime = builder.build().expect("could not build");
+            let task = runtime.spawn(futures::future::pending::<()>());
+            assert!(matches!(
+                runtime.shutdown_timeout(Duration::from_millis(50)),
+                Err(ProcError::TimedOut)
+            ));
+            assert!(futures::executor::block_on(task)
+                .unwrap_err()
+                .is_cancelled());
         }
     }
 }
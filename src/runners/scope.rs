@@ -0,0 +1,74 @@
+use crate::cancel::CancellationToken;
+use crate::combinators::BlockingProc;
+use crate::runners::thread::ScopedThread;
+
+/// Scope for spawning [`Proc`](crate::Proc)s which may borrow non-`'static` data,
+/// created through [`scope`](crate::scope). Similar to [`std::thread::Scope`].
+pub struct Scope<'scope, 'env: 'scope> {
+    pub(crate) inner: &'scope std::thread::Scope<'scope, 'env>,
+}
+
+impl<'scope, 'env> Scope<'scope, 'env> {
+    /// Executes a function to completion on a native OS thread
+    pub fn thread<F, T>(&self, f: F) -> ScopedThread<'scope, T>
+    where
+        F: FnOnce() -> anyhow::Result<T> + Send + 'scope,
+        T: Send + 'scope,
+    {
+        self.cancellable_thread(move |_| f())
+    }
+
+    /// Executes a function to completion on a native OS thread.
+    /// The function receives a [`CancellationToken`] which is cancelled when the
+    /// [`Proc`](crate::Proc) is forgotten
+    pub fn cancellable_thread<F, T>(&self, f: F) -> ScopedThread<'scope, T>
+    where
+        F: FnOnce(CancellationToken) -> anyhow::Result<T> + Send + 'scope,
+        T: Send + 'scope,
+    {
+        ScopedThread::spawn_scoped(self.inner, f)
+    }
+
+    /// Executes a function to completion using a blocking call
+    pub fn blocking<F, T>(&self, f: F) -> BlockingProc<F, T>
+    where
+        F: FnOnce() -> anyhow::Result<T> + Send + 'scope,
+        T: Send + 'scope,
+    {
+        crate::blocking(f)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::proc::Proc;
+    use crate::proc_ext::ProcExt;
+    use crate::scope;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    #[test]
+    fn borrows_from_stack() {
+        let data = (1..=4).collect::<Vec<u64>>();
+        let (left, right) = data.split_at(2);
+        let sum = scope(|s| {
+            s.thread(|| Ok(left.iter().sum::<u64>()))
+                .zip(s.thread(|| Ok(right.iter().sum::<u64>())))
+                .map(|(left, right)| left + right)
+                .join()
+        })
+        .expect("could not join");
+        assert_eq!(sum, 10);
+    }
+
+    #[test]
+    fn join_on_scope_exit() {
+        let counter = AtomicU64::new(0);
+        scope(|s| {
+            let _ = s
+                .thread(|| Ok(counter.fetch_add(1, Ordering::SeqCst)))
+                .and_then(s.blocking(|| Ok(counter.fetch_add(2, Ordering::SeqCst))))
+                .boxed();
+        });
+        assert_eq!(counter.load(Ordering::Relaxed), 3);
+    }
+}
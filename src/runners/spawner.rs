@@ -0,0 +1,330 @@
+use crate::error::{ProcError, ProcResult};
+use crate::panic::PanicError;
+use flume::r#async::RecvFut;
+use flume::Receiver;
+use futures::future::{AbortHandle, BoxFuture};
+use std::cell::Cell;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::thread::LocalKey;
+
+/// Executor onto which a [`TaskRuntime`](crate::runtime::TaskRuntime) spawns its tasks
+pub trait Spawner: Send + Sync {
+    /// Spawns a future which runs to completion in the background
+    fn spawn_detached(&self, fut: BoxFuture<'static, ()>);
+
+    /// Runs a blocking wait on tasks spawned by this [`Spawner`] on the current thread.
+    /// Fails with [`ProcError::WouldDeadlock`] if this would block the executor driving them
+    fn block_in_place(&self, f: &mut dyn FnMut()) -> ProcResult<()> {
+        f();
+        Ok(())
+    }
+}
+
+/// Handle to a task spawned onto a [`TaskRuntime`](crate::runtime::TaskRuntime),
+/// which resolves to the output of the task
+pub struct TaskHandle<T: 'static> {
+    output: Receiver<Result<T, PanicError>>,
+    recv: RecvFut<'static, Result<T, PanicError>>,
+    abort: AbortHandle,
+}
+
+impl<T: 'static> TaskHandle<T> {
+    pub(crate) fn new(output: Receiver<Result<T, PanicError>>, abort: AbortHandle) -> Self {
+        Self {
+            recv: output.clone().into_recv_async(),
+            output,
+            abort,
+        }
+    }
+
+    /// Aborts the task, resolving the handle to [`ProcError::Cancelled`]
+    #[inline]
+    pub fn abort(&self) {
+        self.abort.abort();
+    }
+
+    #[inline]
+    pub fn abort_handle(&self) -> AbortHandle {
+        self.abort.clone()
+    }
+
+    /// Whether the task completed, was aborted, or its runtime was shut down
+    #[inline]
+    pub fn is_finished(&self) -> bool {
+        !self.output.is_empty() || self.output.is_disconnected()
+    }
+}
+
+impl<T: 'static> Future for TaskHandle<T> {
+    type Output = ProcResult<T>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.recv)
+            .poll(cx)
+            .map(|output| match output {
+                Ok(output) => output.map_err(ProcError::from),
+                // The task was dropped before completion
+                Err(_) => Err(ProcError::Cancelled),
+            })
+    }
+}
+
+/// Marks the current thread in `polling` while it polls a task of the executor `key`,
+/// such that a blocking wait inside the task can tell it would block its own executor
+struct Tracked<K: Copy + 'static> {
+    key: K,
+    polling: &'static LocalKey<Cell<Option<K>>>,
+    fut: BoxFuture<'static, ()>,
+}
+
+impl<K: Copy + 'static> Future for Tracked<K> {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let previous = self.polling.replace(Some(self.key));
+        let output = self.fut.as_mut().poll(cx);
+        self.polling.set(previous);
+        output
+    }
+}
+
+// The key is never pinned
+impl<K: Copy + 'static> Unpin for Tracked<K> {}
+
+#[cfg(feature = "tokio")]
+pub use self::tokio::TokioSpawner;
+
+#[cfg(feature = "tokio")]
+mod tokio {
+    use super::{Spawner, Tracked};
+    use crate::error::{ProcError, ProcResult};
+    use futures::future::BoxFuture;
+    use std::cell::Cell;
+    use tokio::runtime::{Handle, Id, RuntimeFlavor};
+
+    thread_local! {
+        // Runtime of the task which is being polled on the current thread
+        static POLLING: Cell<Option<Id>> = const { Cell::new(None) };
+    }
+
+    /// [`Spawner`] for a tokio runtime
+    #[derive(Clone)]
+    pub struct TokioSpawner(Handle);
+
+    impl TokioSpawner {
+        #[inline]
+        pub fn new(handle: Handle) -> Self {
+            Self(handle)
+        }
+
+        #[inline]
+        pub fn handle(&self) -> &Handle {
+            &self.0
+        }
+
+        /// Whether the current thread drives the current-thread runtime of this spawner,
+        /// either from a task spawned through a [`TokioSpawner`] or from outside any task,
+        /// i.e. the future passed to [`Runtime::block_on`](tokio::runtime::Runtime::block_on).
+        /// Blocking threads always run inside a task of their own, so they are never mistaken
+        /// for the driver
+        fn is_driving(&self) -> bool {
+            POLLING.get() == Some(self.0.id()) || tokio::task::try_id().is_none()
+        }
+    }
+
+    impl Spawner for TokioSpawner {
+        fn spawn_detached(&self, fut: BoxFuture<'static, ()>) {
+            self.0.spawn(Tracked {
+                key: self.0.id(),
+                polling: &POLLING,
+                fut,
+            });
+        }
+
+        /// Inside a multi-threaded runtime, the executor thread is handed off through
+        /// [`tokio::task::block_in_place`]. Blocking the thread which drives a current-thread
+        /// runtime fails instead, while its blocking threads may wait on it. Tasks which were
+        /// spawned onto the runtime without a [`TokioSpawner`] are not detected
+        fn block_in_place(&self, f: &mut dyn FnMut()) -> ProcResult<()> {
+            let Ok(current) = Handle::try_current() else {
+                f();
+                return Ok(());
+            };
+            match current.runtime_flavor() {
+                RuntimeFlavor::CurrentThread if current.id() == self.0.id() => {
+                    if self.is_driving() {
+                        return Err(ProcError::WouldDeadlock);
+                    }
+                    f()
+                }
+                RuntimeFlavor::CurrentThread => f(),
+                _ => tokio::task::block_in_place(f),
+            }
+            Ok(())
+        }
+    }
+}
+
+#[cfg(feature = "smol")]
+pub use self::smol::SmolSpawner;
+
+#[cfg(feature = "smol")]
+mod smol {
+    use super::{Spawner, Tracked};
+    use crate::error::{ProcError, ProcResult};
+    use futures::future::BoxFuture;
+    use smol::Executor;
+    use std::cell::Cell;
+    use std::sync::{Arc, Weak};
+
+    thread_local! {
+        // Executor of the task which is being polled on the current thread
+        static POLLING: Cell<Option<usize>> = const { Cell::new(None) };
+    }
+
+    #[derive(Clone)]
+    enum Target {
+        Global,
+        Shared(Arc<Executor<'static>>),
+        // Tasks are dropped together with the executor once its thread exits
+        Dedicated(Weak<Executor<'static>>),
+    }
+
+    impl Target {
+        fn id(&self) -> usize {
+            match self {
+                Self::Global => 0,
+                Self::Shared(executor) => Arc::as_ptr(executor) as usize,
+                Self::Dedicated(executor) => Weak::as_ptr(executor) as usize,
+            }
+        }
+    }
+
+    /// [`Spawner`] for a smol executor
+    #[derive(Clone)]
+    pub struct SmolSpawner(Target);
+
+    impl SmolSpawner {
+        /// Spawns onto the global executor of smol, see [`smol::spawn`]
+        #[inline]
+        pub fn global() -> Self {
+            Self(Target::Global)
+        }
+
+        /// Spawns onto an executor which must be driven by the caller
+        #[inline]
+        pub fn new(executor: Arc<Executor<'static>>) -> Self {
+            Self(Target::Shared(executor))
+        }
+
+        #[inline]
+        pub(crate) fn dedicated(executor: &Arc<Executor<'static>>) -> Self {
+            Self(Target::Dedicated(Arc::downgrade(executor)))
+        }
+    }
+
+    impl Spawner for SmolSpawner {
+        fn spawn_detached(&self, fut: BoxFuture<'static, ()>) {
+            let fut = Tracked {
+                key: self.0.id(),
+                polling: &POLLING,
+                fut,
+            };
+            match &self.0 {
+                Target::Global => smol::spawn(fut).detach(),
+                Target::Shared(executor) => executor.spawn(fut).detach(),
+                Target::Dedicated(executor) => {
+                    if let Some(executor) = executor.upgrade() {
+                        executor.spawn(fut).detach();
+                    }
+                }
+            }
+        }
+
+        /// Blocking inside a task of the same executor fails, as smol cannot hand off
+        /// the thread running it. Only tasks spawned through a [`SmolSpawner`] are detected.
+        fn block_in_place(&self, f: &mut dyn FnMut()) -> ProcResult<()> {
+            if POLLING.get() == Some(self.0.id()) {
+                return Err(ProcError::WouldDeadlock);
+            }
+            f();
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::error::ProcError;
+    use crate::runtime::TaskRuntime;
+
+    fn runtimes() -> Vec<TaskRuntime> {
+        vec![
+            #[cfg(feature = "tokio")]
+            TaskRuntime::tokio(),
+            #[cfg(feature = "smol")]
+            TaskRuntime::smol(),
+        ]
+    }
+
+    #[test]
+    fn spawn() {
+        for runtime in runtimes() {
+            let output = futures::executor::block_on(runtime.spawn(async { 1 }));
+            assert_eq!(output.expect("could not join"), 1);
+        }
+    }
+
+    #[test]
+    fn panicked() {
+        for runtime in runtimes() {
+            let output = futures::executor::block_on(runtime.spawn(async { panic!("boom") }));
+            assert!(matches!(output, Err(ProcError::Panicked(panic)) if panic.message() == "boom"));
+        }
+    }
+
+    #[test]
+    fn abort() {
+        for runtime in runtimes() {
+            let task = runtime.spawn(futures::future::pending::<()>());
+            task.abort();
+            assert!(futures::executor::block_on(task)
+                .unwrap_err()
+                .is_cancelled());
+        }
+    }
+
+    #[cfg(feature = "smol")]
+    #[test]
+    fn smol_would_deadlock() {
+        use crate::runners::{SmolSpawner, Spawner};
+        use std::sync::Arc;
+
+        let executor = Arc::new(smol::Executor::new());
+        let spawner = SmolSpawner::new(executor.clone());
+        let inner = spawner.clone();
+        let (tx, rx) = flume::bounded(1);
+        spawner.spawn_detached(Box::pin(async move {
+            let _ = tx.send(inner.block_in_place(&mut || {}));
+        }));
+        let blocked = smol::block_on(executor.run(rx.recv_async())).unwrap();
+        assert!(matches!(blocked, Err(ProcError::WouldDeadlock)));
+        assert!(spawner.block_in_place(&mut || {}).is_ok());
+    }
+
+    #[test]
+    fn shutdown() {
+        for mut runtime in runtimes() {
+            let task = runtime.spawn(futures::future::pending::<()>());
+            runtime.shutdown();
+            assert!(futures::executor::block_on(task)
+                .unwrap_err()
+                .is_cancelled());
+            assert!(futures::executor::block_on(runtime.spawn(async {}))
+                .unwrap_err()
+                .is_cancelled());
+        }
+    }
+}
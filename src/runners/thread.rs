@@ -1,26 +1,233 @@
-use crate::proc::Proc;
-use std::thread::JoinHandle;
+use crate::cancel::CancellationToken;
+use crate::drop_policy::DropPolicy;
+use crate::error::{ProcError, ProcResult};
+use crate::panic::{catch_unwind, PanicError};
+use crate::proc::{Proc, ProcStatus};
+use flume::{Receiver, RecvTimeoutError};
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::task::Waker;
+use std::thread::{JoinHandle, ScopedJoinHandle};
+use std::time::Instant;
+
+type ThreadOutput<T> = Result<anyhow::Result<T>, PanicError>;
+type SharedWaker = Arc<Mutex<Option<Waker>>>;
+
+/// Handle of a native OS thread backing a [`NativeThread`]
+pub trait ThreadHandle: Send {
+    fn is_finished(&self) -> bool;
+    fn join(self) -> std::thread::Result<()>;
+}
+
+impl ThreadHandle for JoinHandle<()> {
+    #[inline]
+    fn is_finished(&self) -> bool {
+        JoinHandle::is_finished(self)
+    }
+
+    #[inline]
+    fn join(self) -> std::thread::Result<()> {
+        JoinHandle::join(self)
+    }
+}
+
+impl ThreadHandle for ScopedJoinHandle<'_, ()> {
+    #[inline]
+    fn is_finished(&self) -> bool {
+        ScopedJoinHandle::is_finished(self)
+    }
+
+    #[inline]
+    fn join(self) -> std::thread::Result<()> {
+        ScopedJoinHandle::join(self)
+    }
+}
 
 /// Instance of a [`Proc`] which runs offloads a callable into a native OS thread
-pub struct NativeThread<T: Send>(pub(crate) Option<JoinHandle<anyhow::Result<T>>>);
+pub struct NativeThread<T: Send, H: ThreadHandle = JoinHandle<()>> {
+    handle: Option<H>,
+    output: Receiver<ThreadOutput<T>>,
+    token: CancellationToken,
+    waker: SharedWaker,
+    drop_policy: DropPolicy,
+}
+
+/// Instance of a [`Proc`] which offloads a callable into a native OS thread,
+/// which may borrow from its enclosing [`Scope`](crate::Scope)
+pub type ScopedThread<'scope, T> = NativeThread<T, ScopedJoinHandle<'scope, ()>>;
+
+/// Wraps `f` into a thread body which reports its output, or panic, over a channel
+/// and wakes the registered [`Waker`], if any
+fn thread_body<F, T>(
+    f: F,
+) -> (
+    impl FnOnce() + Send,
+    Receiver<ThreadOutput<T>>,
+    CancellationToken,
+    SharedWaker,
+)
+where
+    F: FnOnce(CancellationToken) -> anyhow::Result<T> + Send,
+    T: Send,
+{
+    let token = CancellationToken::new();
+    let ctx = token.clone();
+    let (output_tx, output) = flume::bounded(1);
+    let waker = SharedWaker::default();
+    let notify = waker.clone();
+    let body = move || {
+        let _ = output_tx.send(catch_unwind(|| f(ctx)));
+        if let Some(waker) = notify.lock().unwrap().take() {
+            waker.wake();
+        }
+    };
+    (body, output, token, waker)
+}
+
+impl<T: Send + 'static> NativeThread<T> {
+    pub(crate) fn spawn<F>(f: F) -> Self
+    where
+        F: FnOnce(CancellationToken) -> anyhow::Result<T> + Send + 'static,
+    {
+        Self::spawn_with(std::thread::Builder::new(), f).expect("failed to spawn thread")
+    }
+
+    pub(crate) fn spawn_with<F>(builder: std::thread::Builder, f: F) -> io::Result<Self>
+    where
+        F: FnOnce(CancellationToken) -> anyhow::Result<T> + Send + 'static,
+    {
+        let (body, output, token, waker) = thread_body(f);
+        Ok(Self {
+            handle: Some(builder.spawn(body)?),
+            output,
+            token,
+            waker,
+            drop_policy: DropPolicy::default(),
+        })
+    }
+}
+
+impl<'scope, T: Send + 'scope> ScopedThread<'scope, T> {
+    pub(crate) fn spawn_scoped<F>(scope: &'scope std::thread::Scope<'scope, '_>, f: F) -> Self
+    where
+        F: FnOnce(CancellationToken) -> anyhow::Result<T> + Send + 'scope,
+    {
+        let (body, output, token, waker) = thread_body(f);
+        Self {
+            handle: Some(scope.spawn(body)),
+            output,
+            token,
+            waker,
+            drop_policy: DropPolicy::default(),
+        }
+    }
+}
 
-impl<T: Send> Proc for NativeThread<T> {
+impl<T: Send, H: ThreadHandle> NativeThread<T, H> {
+    /// Joins the finished thread and unpacks its output
+    fn finish(&mut self, output: Option<ThreadOutput<T>>) -> ProcResult<T> {
+        let handle = self.handle.take().ok_or(ProcError::AlreadyJoined)?;
+        let exit = handle.join();
+        match output {
+            Some(output) => output?.map_err(ProcError::from),
+            None => Err(exit.map_or_else(ProcError::from_panic, |_| ProcError::Cancelled)),
+        }
+    }
+}
+
+impl<T: Send, H: ThreadHandle> Proc for NativeThread<T, H> {
     type Output = T;
 
-    fn join(&mut self) -> anyhow::Result<Self::Output> {
-        if let Some(t) = self.0.take() {
-            t.join()
-                .map_err(|_err| anyhow::anyhow!("Could not join error"))?
-        } else {
-            Err(anyhow::anyhow!("Nothing to join"))
+    fn join(&mut self) -> ProcResult<Self::Output> {
+        if self.handle.is_none() {
+            return Err(ProcError::AlreadyJoined);
         }
+        let output = self.output.recv().ok();
+        self.finish(output)
+    }
+
+    fn join_deadline(&mut self, deadline: Instant) -> ProcResult<Self::Output> {
+        if self.handle.is_none() {
+            return Err(ProcError::AlreadyJoined);
+        }
+        match self.output.recv_deadline(deadline) {
+            Ok(output) => self.finish(Some(output)),
+            Err(RecvTimeoutError::Timeout) => Err(ProcError::TimedOut),
+            Err(RecvTimeoutError::Disconnected) => self.finish(None),
+        }
+    }
+
+    fn status(&self) -> ProcStatus {
+        match &self.handle {
+            None => ProcStatus::Joined,
+            Some(_) if self.token.is_cancelled() => ProcStatus::Forgotten,
+            Some(handle) if handle.is_finished() || !self.output.is_empty() => ProcStatus::Finished,
+            Some(_) => ProcStatus::Running,
+        }
+    }
+
+    /// Requests cancellation of the thread, which is detached when dropped
+    fn forget(&mut self) {
+        self.token.cancel();
+    }
+
+    fn set_drop_policy(&mut self, policy: DropPolicy) {
+        self.drop_policy = policy;
     }
 
-    fn forget(&mut self) {}
+    fn register_waker(&mut self, waker: &Waker) -> bool {
+        *self.waker.lock().unwrap() = Some(waker.clone());
+        true
+    }
 }
 
-impl<T: Send> Drop for NativeThread<T> {
+impl<T: Send, H: ThreadHandle> Drop for NativeThread<T, H> {
     fn drop(&mut self) {
-        let _ = self.join();
+        self.drop_policy.apply(self);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::error::ProcError;
+    use crate::proc::{Proc, ProcStatus};
+    use crate::{cancellable_thread, thread};
+    use std::task::Poll;
+    use std::time::Duration;
+
+    #[test]
+    fn join_timeout() {
+        let mut proc = cancellable_thread(|token| {
+            token.wait();
+            Ok(1)
+        });
+        assert!(matches!(
+            proc.join_timeout(Duration::from_millis(10)),
+            Err(ProcError::TimedOut)
+        ));
+        proc.forget();
+        assert_eq!(proc.join().expect("could not join"), 1);
+    }
+
+    #[test]
+    fn try_join() {
+        let (tx, rx) = flume::bounded::<()>(0);
+        let mut proc = thread(move || Ok(rx.recv()?));
+        assert!(proc.try_join().is_pending());
+        assert_eq!(proc.status(), ProcStatus::Running);
+        tx.send(()).unwrap();
+        while !proc.is_finished() {
+            std::thread::yield_now();
+        }
+        assert!(matches!(proc.try_join(), Poll::Ready(Ok(()))));
+        assert_eq!(proc.status(), ProcStatus::Joined);
+    }
+
+    #[test]
+    fn join_twice() {
+        let mut proc = thread(|| Ok(()));
+        proc.join_timeout(Duration::from_secs(5))
+            .expect("could not join");
+        assert!(matches!(proc.join(), Err(ProcError::AlreadyJoined)));
     }
 }
@@ -0,0 +1,23 @@
+//! Runs the unit tests for every supported combination of features, as the core must also
+//! build without any executor backend. Invokes cargo once per combination, so it is ignored
+//! by default: `cargo test --test features -- --ignored`
+use std::process::Command;
+
+const FEATURES: &[&str] = &["", "tokio", "smol", "tokio,smol"];
+
+#[test]
+#[ignore = "builds the crate once per feature combination"]
+fn feature_matrix() {
+    for features in FEATURES {
+        let mut cargo = Command::new(env!("CARGO"));
+        cargo
+            .current_dir(env!("CARGO_MANIFEST_DIR"))
+            .args(["test", "--lib", "--no-default-features"])
+            .args(["--target-dir", env!("CARGO_TARGET_TMPDIR")]);
+        if !features.is_empty() {
+            cargo.args(["--features", features]);
+        }
+        let status = cargo.status().expect("could not run cargo");
+        assert!(status.success(), "tests failed with features [{features}]");
+    }
+}
@@ -0,0 +1,58 @@
+//! The global runtime is shared by the whole process, so it is tested in isolation
+#![cfg(feature = "async")]
+
+use circuits::runtime::TaskRuntime;
+use circuits::{JoinTasks, Proc, ProcError};
+use std::sync::{Arc, Barrier};
+use std::time::Duration;
+
+fn rendezvous(workers: usize) -> JoinTasks<()> {
+    let barrier = Arc::new(Barrier::new(workers));
+    (0..workers).fold(JoinTasks::new(), |tasks, _| {
+        let barrier = barrier.clone();
+        tasks.and(async move {
+            barrier.wait();
+        })
+    })
+}
+
+#[test]
+fn global_runtime() {
+    // Tasks blocking their worker can only meet if each runs on its own worker
+    TaskRuntime::set_global_workers(3);
+    rendezvous(3)
+        .join_timeout(Duration::from_secs(5))
+        .expect("could not join");
+
+    // Shutting down from one of its own tasks would join the calling thread
+    let task = TaskRuntime::global().spawn(async { TaskRuntime::shutdown_global() });
+    let output = futures::executor::block_on(task).expect("could not join");
+    assert!(matches!(output, Err(ProcError::WouldDeadlock)));
+
+    // Handles only wait on the tasks spawned through them
+    let (tx, rx) = flume::bounded::<()>(0);
+    let busy = TaskRuntime::global();
+    let task = busy.spawn(async move {
+        let _ = rx.recv_async().await;
+    });
+    TaskRuntime::global()
+        .shutdown_timeout(Duration::from_millis(10))
+        .expect("could not shut down");
+    drop(tx);
+    futures::executor::block_on(task).expect("could not join");
+
+    let (tx, rx) = flume::bounded::<()>(0);
+    let mut pending = JoinTasks::new().and(async move {
+        let _ = rx.recv_async().await;
+    });
+    TaskRuntime::shutdown_global().expect("could not shut down");
+    assert!(matches!(pending.join(), Err(ProcError::RuntimeShutdown)));
+    drop(tx);
+
+    // Restarts on next use with the updated configuration
+    TaskRuntime::set_global_workers(2);
+    rendezvous(2)
+        .join_timeout(Duration::from_secs(5))
+        .expect("could not join");
+    TaskRuntime::shutdown_global().expect("could not shut down");
+}